@@ -0,0 +1,90 @@
+use serde_json::{json, Map, Value};
+
+/// Returns the operations of a JSON Patch (RFC 6902) which transforms the old into the new value.
+/// Objects are compared by their keys and arrays by their indices, everything else is replaced as a whole.
+pub fn diff(old: &Value, new: &Value) -> Vec<Value> {
+    let mut operations = vec![];
+    diff_at("", old, new, &mut operations);
+    operations
+}
+
+fn diff_at(path: &str, old: &Value, new: &Value, operations: &mut Vec<Value>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => { diff_objects(path, old, new, operations) }
+        (Value::Array(old), Value::Array(new)) => {
+            for (i, (old, new)) in old.iter().zip(new).enumerate() {
+                diff_at(&format!("{path}/{i}"), old, new, operations);
+            }
+
+            // removed from the back, so the indices of the other removals stay valid
+            for i in (new.len()..old.len()).rev() {
+                operations.push(json!({ "op": "remove", "path": format!("{path}/{i}") }));
+            }
+
+            for (i, value) in new.iter().enumerate().skip(old.len()) {
+                operations.push(json!({ "op": "add", "path": format!("{path}/{i}"), "value": value }));
+            }
+        }
+        (old, new) => {
+            if old != new { operations.push(json!({ "op": "replace", "path": path, "value": new })) }
+        }
+    }
+}
+
+fn diff_objects(path: &str, old: &Map<String, Value>, new: &Map<String, Value>, operations: &mut Vec<Value>) {
+    for (key, old) in old {
+        let path = format!("{path}/{}", escape(key));
+
+        match new.get(key) {
+            Some(new) => { diff_at(&path, old, new, operations) }
+            None => { operations.push(json!({ "op": "remove", "path": path })) }
+        }
+    }
+
+    for (key, value) in new.iter().filter(|(key, _)| !old.contains_key(*key)) {
+        operations.push(json!({ "op": "add", "path": format!("{path}/{}", escape(key)), "value": value }));
+    }
+}
+
+/// Escapes a key to be used as a reference token of a JSON Pointer (RFC 6901)
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::diff;
+
+    #[test]
+    fn diffs_objects() {
+        let old = json!({ "title": "one", "floating": false, "size": [1, 2], "a/b": 1 });
+        let new = json!({ "title": "two", "size": [1, 3], "a/b": 1, "pinned": true });
+
+        assert_eq!(diff(&old, &new), vec![
+            json!({ "op": "remove", "path": "/floating" }),
+            json!({ "op": "replace", "path": "/size/1", "value": 3 }),
+            json!({ "op": "replace", "path": "/title", "value": "two" }),
+            json!({ "op": "add", "path": "/pinned", "value": true }),
+        ]);
+    }
+
+    #[test]
+    fn diffs_arrays() {
+        let old = json!([{ "id": 1 }, { "id": 2 }, { "id": 3 }]);
+
+        assert_eq!(diff(&old, &json!([{ "id": 1 }])), vec![
+            json!({ "op": "remove", "path": "/2" }),
+            json!({ "op": "remove", "path": "/1" }),
+        ]);
+        assert_eq!(diff(&old, &json!([{ "id": 1 }, { "id": 2 }, { "id": 3 }, { "id": 4 }])), vec![
+            json!({ "op": "add", "path": "/3", "value": { "id": 4 } }),
+        ]);
+        assert_eq!(diff(&old, &old), Vec::<serde_json::Value>::new());
+    }
+
+    #[test]
+    fn replaces_whole_documents() {
+        assert_eq!(diff(&json!(null), &json!([1])), vec![json!({ "op": "replace", "path": "", "value": [1] })]);
+    }
+}
@@ -0,0 +1,362 @@
+use anyhow::anyhow;
+use regex::Regex;
+use serde_json::Value;
+
+/// Filter expression which is evaluated against every item of the data, e.g. `class =~ "firefox" && floating == false && workspace.id > 0`.
+///
+/// Expressions consist of:
+/// - paths to fields, separated by dots (e.g. `workspace.name`, or `grouped.0` for items of lists)
+/// - literals, which are strings in double quotes, numbers, `true`, `false` and `null`
+/// - comparisons with `==`, `!=`, `<`, `<=`, `>`, `>=` and `=~` (matches a regex)
+/// - logic with `&&`, `||`, `!` and parentheses
+///
+/// A value on its own is true unless it is `false`, `null` or does not exist.
+#[derive(Clone, Debug)]
+pub struct Filter {
+    expression: Expression
+}
+
+#[derive(Clone, Debug)]
+enum Expression {
+    Path(Vec<String>),
+    Literal(Value),
+    Compare(Box<Expression>, Comparison, Box<Expression>),
+    Matches(Box<Expression>, Regex),
+    Not(Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Comparison {
+    Equal, NotEqual, Less, LessEqual, Greater, GreaterEqual
+}
+
+impl Filter {
+    /// Parses the filter expression
+    pub fn parse(expression: &str) -> anyhow::Result<Self> {
+        let mut parser = Parser { tokens: tokenize(expression)?, position: 0, end: expression.len() };
+
+        let expression = parser.or()?;
+        if let Some((token, position)) = parser.tokens.get(parser.position) {
+            return Err(anyhow!("unexpected {token} at position {position}, expected && or ||"));
+        }
+
+        Ok(Self { expression })
+    }
+
+    /// Returns whether the item matches the filter
+    pub fn matches(&self, item: &Value) -> bool {
+        truthy(&self.expression.evaluate(item))
+    }
+
+    /// Only keeps the items of a list which match the filter, other data is replaced by null if it does not match
+    pub fn apply(&self, data: Value) -> Value {
+        match data {
+            Value::Array(items) => { Value::Array(items.into_iter().filter(|item| self.matches(item)).collect()) }
+            data => { if self.matches(&data) { data } else { Value::Null } }
+        }
+    }
+}
+
+impl Expression {
+    fn evaluate(&self, item: &Value) -> Value {
+        match self {
+            Expression::Path(path) => {
+                path.iter().try_fold(item, |value, key| match value {
+                    Value::Object(object) => { object.get(key) }
+                    Value::Array(list) => { key.parse::<usize>().ok().and_then(|i| list.get(i)) }
+                    _ => None
+                }).cloned().unwrap_or(Value::Null)
+            }
+            Expression::Literal(value) => { value.clone() }
+            Expression::Compare(left, comparison, right) => {
+                Value::Bool(compare(&left.evaluate(item), *comparison, &right.evaluate(item)))
+            }
+            Expression::Matches(value, regex) => {
+                Value::Bool(value.evaluate(item).as_str().is_some_and(|s| regex.is_match(s)))
+            }
+            Expression::Not(expression) => { Value::Bool(!truthy(&expression.evaluate(item))) }
+            Expression::And(left, right) => { Value::Bool(truthy(&left.evaluate(item)) && truthy(&right.evaluate(item))) }
+            Expression::Or(left, right) => { Value::Bool(truthy(&left.evaluate(item)) || truthy(&right.evaluate(item))) }
+        }
+    }
+}
+
+fn truthy(value: &Value) -> bool {
+    !matches!(value, Value::Null | Value::Bool(false))
+}
+
+/// Compares two values, numbers by their value and strings lexicographically. Values of different types are only unequal.
+fn compare(left: &Value, comparison: Comparison, right: &Value) -> bool {
+    let ordering = match (left, right) {
+        (Value::Number(l), Value::Number(r)) => { l.as_f64().zip(r.as_f64()).and_then(|(l, r)| l.partial_cmp(&r)) }
+        (Value::String(l), Value::String(r)) => { Some(l.cmp(r)) }
+        (l, r) => { if l == r { Some(std::cmp::Ordering::Equal) } else { None } }
+    };
+
+    match comparison {
+        Comparison::Equal => { ordering.is_some_and(|o| o.is_eq()) }
+        Comparison::NotEqual => { !ordering.is_some_and(|o| o.is_eq()) }
+        Comparison::Less => { ordering.is_some_and(|o| o.is_lt()) }
+        Comparison::LessEqual => { ordering.is_some_and(|o| o.is_le()) }
+        Comparison::Greater => { ordering.is_some_and(|o| o.is_gt()) }
+        Comparison::GreaterEqual => { ordering.is_some_and(|o| o.is_ge()) }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Path(Vec<String>),
+    Literal(Value),
+    Operator(&'static str),
+    Open,
+    Close,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Path(path) => { write!(f, "'{}'", path.join(".")) }
+            Token::Literal(value) => { write!(f, "{value}") }
+            Token::Operator(operator) => { write!(f, "'{operator}'") }
+            Token::Open => { write!(f, "'('") }
+            Token::Close => { write!(f, "')'") }
+        }
+    }
+}
+
+const OPERATORS: [&str; 12] = ["==", "!=", "<=", ">=", "=~", "&&", "||", "<", ">", "!", "(", ")"];
+
+/// Splits the expression into tokens together with their position
+fn tokenize(expression: &str) -> anyhow::Result<Vec<(Token, usize)>> {
+    let mut tokens = vec![];
+    let mut chars = expression.char_indices().peekable();
+
+    while let Some(&(position, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+
+            let mut string = String::new();
+            loop {
+                match chars.next() {
+                    Some((_, '"')) => { break }
+                    Some((_, '\\')) => {
+                        match chars.next() {
+                            Some((_, 'n')) => { string.push('\n') }
+                            Some((_, c)) => { string.push(c) }
+                            None => { return Err(anyhow!("unterminated string at position {position}")) }
+                        }
+                    }
+                    Some((_, c)) => { string.push(c) }
+                    None => { return Err(anyhow!("unterminated string at position {position}")) }
+                }
+            }
+
+            tokens.push((Token::Literal(Value::String(string)), position));
+        } else if c.is_ascii_digit() || c == '-' {
+            let word = take_while(&mut chars, |c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '+');
+            let number = serde_json::from_str::<serde_json::Number>(&word).map_err(|_| anyhow!("invalid number '{word}' at position {position}"))?;
+
+            tokens.push((Token::Literal(Value::Number(number)), position));
+        } else if c.is_alphabetic() || c == '_' {
+            let word = take_while(&mut chars, |c| c.is_alphanumeric() || c == '_' || c == '.');
+
+            let token = match word.as_str() {
+                "true" => { Token::Literal(Value::Bool(true)) }
+                "false" => { Token::Literal(Value::Bool(false)) }
+                "null" => { Token::Literal(Value::Null) }
+                _ => {
+                    let path: Vec<String> = word.split('.').map(String::from).collect();
+                    if path.iter().any(String::is_empty) { return Err(anyhow!("invalid path '{word}' at position {position}")) }
+
+                    Token::Path(path)
+                }
+            };
+
+            tokens.push((token, position));
+        } else {
+            let rest = &expression[position..];
+            let Some(operator) = OPERATORS.iter().find(|o| rest.starts_with(**o)) else {
+                return Err(anyhow!("unexpected character '{c}' at position {position}"));
+            };
+
+            for _ in 0..operator.len() { chars.next(); }
+
+            let token = match *operator {
+                "(" => { Token::Open }
+                ")" => { Token::Close }
+                operator => { Token::Operator(operator) }
+            };
+
+            tokens.push((token, position));
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn take_while(chars: &mut std::iter::Peekable<std::str::CharIndices>, predicate: impl Fn(char) -> bool) -> String {
+    let mut word = String::new();
+
+    while let Some(&(_, c)) = chars.peek() {
+        if !predicate(c) { break }
+
+        word.push(c);
+        chars.next();
+    }
+
+    word
+}
+
+/// Recursive descent parser over the tokens, with the precedence `||` < `&&` < `!` < comparisons
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    /// Position of the end of the expression, for errors
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    /// Consumes the next token if it is the given operator
+    fn eat(&mut self, operator: &'static str) -> bool {
+        let found = self.peek() == Some(&Token::Operator(operator));
+
+        if found { self.position += 1 }
+        found
+    }
+
+    fn or(&mut self) -> anyhow::Result<Expression> {
+        let mut expression = self.and()?;
+        while self.eat("||") {
+            expression = Expression::Or(Box::new(expression), Box::new(self.and()?));
+        }
+
+        Ok(expression)
+    }
+
+    fn and(&mut self) -> anyhow::Result<Expression> {
+        let mut expression = self.not()?;
+        while self.eat("&&") {
+            expression = Expression::And(Box::new(expression), Box::new(self.not()?));
+        }
+
+        Ok(expression)
+    }
+
+    fn not(&mut self) -> anyhow::Result<Expression> {
+        if self.eat("!") { return Ok(Expression::Not(Box::new(self.not()?))) }
+
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> anyhow::Result<Expression> {
+        let left = self.value()?;
+
+        let comparison = match self.peek() {
+            Some(Token::Operator("==")) => { Comparison::Equal }
+            Some(Token::Operator("!=")) => { Comparison::NotEqual }
+            Some(Token::Operator("<")) => { Comparison::Less }
+            Some(Token::Operator("<=")) => { Comparison::LessEqual }
+            Some(Token::Operator(">")) => { Comparison::Greater }
+            Some(Token::Operator(">=")) => { Comparison::GreaterEqual }
+            Some(Token::Operator("=~")) => {
+                let position = self.tokens[self.position].1;
+                self.position += 1;
+
+                let Some((Token::Literal(Value::String(pattern)), _)) = self.tokens.get(self.position).cloned() else {
+                    return Err(anyhow!("expected a string with a regex after '=~' at position {position}"));
+                };
+                self.position += 1;
+
+                let regex = Regex::new(&pattern).map_err(|e| anyhow!("invalid regex at position {position}: {e}"))?;
+                return Ok(Expression::Matches(Box::new(left), regex));
+            }
+            _ => { return Ok(left) }
+        };
+
+        self.position += 1;
+        Ok(Expression::Compare(Box::new(left), comparison, Box::new(self.value()?)))
+    }
+
+    fn value(&mut self) -> anyhow::Result<Expression> {
+        let Some((token, position)) = self.tokens.get(self.position).cloned() else {
+            return Err(anyhow!("unexpected end of the filter at position {}, expected a value", self.end));
+        };
+        self.position += 1;
+
+        match token {
+            Token::Path(path) => { Ok(Expression::Path(path)) }
+            Token::Literal(value) => { Ok(Expression::Literal(value)) }
+            Token::Open => {
+                let expression = self.or()?;
+
+                match self.tokens.get(self.position) {
+                    Some((Token::Close, _)) => { self.position += 1; Ok(expression) }
+                    _ => { Err(anyhow!("unclosed '(' at position {position}")) }
+                }
+            }
+            token => { Err(anyhow!("unexpected {token} at position {position}, expected a value")) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::Filter;
+
+    fn matches(filter: &str, item: serde_json::Value) -> bool {
+        Filter::parse(filter).unwrap().matches(&item)
+    }
+
+    #[test]
+    fn compares_values() {
+        let client = json!({ "class": "firefox", "floating": false, "pid": 42, "workspace": { "id": 3, "name": "web" }, "grouped": ["0x1"] });
+
+        assert!(matches("class == \"firefox\"", client.clone()));
+        assert!(matches("floating == false && pid >= 42 && pid < 42.5", client.clone()));
+        assert!(matches("workspace.id > 0 && workspace.name != \"1\"", client.clone()));
+        assert!(matches("grouped.0 == \"0x1\" && grouped.1 == null", client.clone()));
+        assert!(!matches("class > 1", client.clone()));
+        assert!(!matches("missing.field", client));
+    }
+
+    #[test]
+    fn evaluates_logic() {
+        let client = json!({ "class": "kitty", "floating": true, "pinned": false });
+
+        assert!(matches("class =~ \"^(kitty|foot)$\" && floating", client.clone()));
+        assert!(matches("!pinned && (class == \"foot\" || floating)", client.clone()));
+        assert!(!matches("!(class == \"foot\" || floating)", client.clone()));
+        assert!(matches("pinned || class == \"foot\" || class =~ \"it\"", client));
+    }
+
+    #[test]
+    fn filters_lists() {
+        let workspaces = json!([{ "id": -98, "name": "special:magic" }, { "id": 1, "name": "1" }, { "id": 2, "name": "2" }]);
+        let filter = Filter::parse("id > 0").unwrap();
+
+        assert_eq!(filter.apply(workspaces), json!([{ "id": 1, "name": "1" }, { "id": 2, "name": "2" }]));
+        assert_eq!(filter.apply(json!({ "id": -1 })), json!(null));
+    }
+
+    #[test]
+    fn reports_parse_errors() {
+        let error = |filter: &str| Filter::parse(filter).unwrap_err().to_string();
+
+        assert_eq!(error("class == "), "unexpected end of the filter at position 9, expected a value");
+        assert_eq!(error("class == \"a"), "unterminated string at position 9");
+        assert_eq!(error("(floating"), "unclosed '(' at position 0");
+        assert_eq!(error("floating pinned"), "unexpected 'pinned' at position 9, expected && or ||");
+        assert_eq!(error("class =~ 1"), "expected a string with a regex after '=~' at position 6");
+        assert_eq!(error("class =~ \"(\"").split(':').next(), Some("invalid regex at position 6"));
+        assert_eq!(error("class = 1"), "unexpected character '=' at position 6");
+    }
+}
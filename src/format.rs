@@ -0,0 +1,247 @@
+use anyhow::{anyhow, Context};
+use clap::Args;
+use handlebars::{no_escape, Handlebars};
+use serde_json::{Map, Value};
+
+/// Template to render data as text, written in the handlebars language (e.g. `{{#each this}}{{name}} {{/each}}`).
+/// Besides the builtin helpers (`if`, `each`, `eq`, `and`, etc.), the following are available:
+/// - `join list separator [field="name"]` joins the items of a list, or the given field of them
+/// - `pad value width [fill=" "]` pads a value at the end to the given width
+/// - `lpad value width [fill=" "]` pads a value at the start to the given width
+#[derive(Clone, Debug)]
+pub struct Template {
+    registry: Handlebars<'static>
+}
+
+impl Template {
+    /// Parses the template
+    pub fn new(template: &str) -> anyhow::Result<Self> {
+        Self::with_escape(template, no_escape)
+    }
+
+    /// Parses a template which produces yuck literals, where `{{value}}` is escaped to be placed in a string literal (`"{{value}}"`)
+    /// Use `{{{value}}}` to insert a value without escaping
+    pub fn yuck(template: &str) -> anyhow::Result<Self> {
+        Self::with_escape(template, escape_yuck)
+    }
+
+    fn with_escape(template: &str, escape: fn(&str) -> String) -> anyhow::Result<Self> {
+        let mut registry = Handlebars::new();
+        registry.register_escape_fn(escape);
+        registry.register_helper("join", Box::new(helpers::join));
+        registry.register_helper("pad", Box::new(helpers::pad));
+        registry.register_helper("lpad", Box::new(helpers::lpad));
+
+        registry.register_template_string("template", template).map_err(|e| anyhow!("failed to parse template: {e}"))?;
+
+        Ok(Self { registry })
+    }
+
+    /// Renders the data with the template
+    pub fn render(&self, data: &Value) -> anyhow::Result<String> {
+        self.registry.render("template", data).context("failed to render template")
+    }
+}
+
+/// Options to print the data in the format of a custom module of waybar, which expects one object per line
+#[derive(Args, Clone, Debug, Default)]
+#[command(about = None, long_about = None)]
+pub struct WaybarOptions {
+    /// Print every record as an object for a custom waybar module (requires --text)
+    #[clap(long = "waybar", id = "waybar", requires = "text", conflicts_with_all = ["format", "pretty"])]
+    pub enabled: bool,
+    /// Template for the text of the waybar module
+    #[clap(long, value_parser = Template::new, requires = "waybar")]
+    pub text: Option<Template>,
+    /// Template for the tooltip of the waybar module
+    #[clap(long, value_parser = Template::new, requires = "waybar")]
+    pub tooltip: Option<Template>,
+    /// Template for the css classes of the waybar module (separated by whitespace)
+    #[clap(long, value_parser = Template::new, requires = "waybar")]
+    pub class: Option<Template>,
+    /// Template for the alt of the waybar module, which selects the icon of the format-icons
+    #[clap(long, value_parser = Template::new, requires = "waybar")]
+    pub alt: Option<Template>,
+    /// Template for the percentage of the waybar module, has to render to a number
+    #[clap(long, value_parser = Template::new, requires = "waybar")]
+    pub percentage: Option<Template>,
+}
+
+impl WaybarOptions {
+    /// Renders the data into the object expected by waybar, fields which render empty are left out
+    pub fn render(&self, data: &Value) -> anyhow::Result<Value> {
+        let mut module = Map::new();
+
+        let text = self.text.as_ref().map(|t| t.render(data)).transpose()?.unwrap_or_default();
+        module.insert("text".into(), Value::String(text));
+
+        for (field, template) in [("tooltip", &self.tooltip), ("alt", &self.alt)] {
+            let Some(template) = template else { continue };
+
+            let value = template.render(data)?;
+            if !value.is_empty() { module.insert(field.into(), Value::String(value)); }
+        }
+
+        if let Some(template) = &self.class {
+            let classes: Vec<Value> = template.render(data)?.split_whitespace().map(|c| Value::String(c.into())).collect();
+            if !classes.is_empty() { module.insert("class".into(), Value::Array(classes)); }
+        }
+
+        if let Some(template) = &self.percentage {
+            let percentage = template.render(data)?;
+            let percentage = percentage.trim();
+
+            if !percentage.is_empty() {
+                let number: f64 = percentage.parse().map_err(|_| anyhow!("percentage template rendered '{percentage}', which is not a number"))?;
+                module.insert("percentage".into(), Value::from(number.round() as i64));
+            }
+        }
+
+        Ok(Value::Object(module))
+    }
+}
+
+/// Options to print the data as yuck widget literals, which eww can use directly with `deflisten` and `literal`
+#[derive(Args, Clone, Debug, Default)]
+#[command(about = None, long_about = None)]
+pub struct YuckOptions {
+    /// Print every record as a yuck literal, rendering each item with this template (e.g. '(button :onclick "hyprctl dispatch workspace {{id}}" "{{name}}")')
+    #[clap(long, value_parser = Template::yuck, conflicts_with_all = ["format", "pretty", "waybar"])]
+    pub yuck: Option<Template>,
+    /// Template for the widget containing the items, which are inserted with {{items}} [default: "(box {{items}})"]
+    #[clap(long, value_parser = Template::new, requires = "yuck")]
+    pub container: Option<Template>,
+}
+
+impl YuckOptions {
+    /// Renders every item of the data with the item template and wraps them in the container, on a single line
+    /// Data which is not a list is rendered as a single item, or none if it is null
+    pub fn render(&self, data: &Value) -> anyhow::Result<String> {
+        let Some(template) = &self.yuck else { return Err(anyhow!("no template for the yuck items")) };
+
+        let items = match data {
+            Value::Array(items) => { items.iter().map(|item| template.render(item)).collect::<anyhow::Result<Vec<String>>>()? }
+            Value::Null => { vec![] }
+            item => { vec![template.render(item)?] }
+        };
+
+        let context = serde_json::json!({ "items": items.join(" ") });
+        let widget = match &self.container {
+            Some(container) => { container.render(&context)? }
+            None => { format!("(box {})", context["items"].as_str().unwrap_or_default()) }
+        };
+
+        // deflisten reads one literal per line
+        Ok(widget.lines().map(str::trim).filter(|l| !l.is_empty()).collect::<Vec<&str>>().join(" "))
+    }
+}
+
+/// Escapes a value to be placed inside a yuck string literal
+fn escape_yuck(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        match c {
+            '\\' => { escaped.push_str("\\\\") }
+            '"' => { escaped.push_str("\\\"") }
+            '\n' => { escaped.push_str("\\n") }
+            '$' => { escaped.push_str("\\$") }
+            c => { escaped.push(c) }
+        }
+    }
+
+    escaped
+}
+
+mod helpers {
+    use handlebars::handlebars_helper;
+    use serde_json::Value;
+
+    handlebars_helper!(join: |list: array, separator: str, { field: str = "" }| {
+        list.iter()
+            .map(|item| if field.is_empty() { display(item) } else { display(&item[field]) })
+            .collect::<Vec<String>>().join(separator)
+    });
+
+    handlebars_helper!(pad: |value: Json, width: u64, { fill: str = " " }| {
+        let value = display(value);
+        value.clone() + &padding(&value, width, fill)
+    });
+
+    handlebars_helper!(lpad: |value: Json, width: u64, { fill: str = " " }| {
+        let value = display(value);
+        padding(&value, width, fill) + &value
+    });
+
+    /// Returns how a value is displayed by handlebars, strings without quotes and null as nothing
+    fn display(value: &Value) -> String {
+        match value {
+            Value::String(s) => { s.clone() }
+            Value::Null => { String::new() }
+            other => { other.to_string() }
+        }
+    }
+
+    /// Returns the padding needed to extend a value to the given width
+    fn padding(value: &str, width: u64, fill: &str) -> String {
+        fill.repeat((width as usize).saturating_sub(value.chars().count()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::{Template, WaybarOptions, YuckOptions};
+
+    fn render(template: &str, data: serde_json::Value) -> String {
+        Template::new(template).unwrap().render(&data).unwrap()
+    }
+
+    #[test]
+    fn renders_lists() {
+        let workspaces = json!([{ "name": "1", "active": false }, { "name": "2", "active": true }, { "name": "web & mail", "active": false }]);
+
+        assert_eq!(render("{{#each this}}{{name}}{{#if active}}*{{/if}} {{/each}}", workspaces.clone()), "1 2* web & mail ");
+        assert_eq!(render("{{join this \" | \" field=\"name\"}}", workspaces), "1 | 2 | web & mail");
+    }
+
+    #[test]
+    fn pads_values() {
+        assert_eq!(render("[{{pad name 5}}]", json!({ "name": "ab" })), "[ab   ]");
+        assert_eq!(render("[{{lpad id 3 fill=\"0\"}}]", json!({ "id": 7 })), "[007]");
+        assert_eq!(render("[{{pad name 1}}]", json!({ "name": "abc" })), "[abc]");
+    }
+
+    #[test]
+    fn renders_waybar_modules() {
+        let waybar = WaybarOptions {
+            enabled: true,
+            text: Some(Template::new("{{#each this}}{{#if active}}{{name}}{{/if}}{{/each}}").unwrap()),
+            tooltip: Some(Template::new("").unwrap()),
+            class: Some(Template::new("{{#each this}}{{#if active}}{{#if special}}special{{/if}} active{{/if}}{{/each}}").unwrap()),
+            alt: None,
+            percentage: Some(Template::new("{{len this}}0").unwrap()),
+        };
+
+        let workspaces = json!([{ "name": "1", "active": false, "special": false }, { "name": "2", "active": true, "special": false }]);
+        assert_eq!(waybar.render(&workspaces).unwrap(), json!({ "text": "2", "class": ["active"], "percentage": 20 }));
+    }
+
+    #[test]
+    fn renders_yuck_literals() {
+        let yuck = YuckOptions {
+            yuck: Some(Template::yuck("(button :onclick \"hyprctl dispatch workspace {{id}}\"\n  \"{{name}}\")").unwrap()),
+            container: None,
+        };
+
+        let workspaces = json!([{ "id": 1, "name": "1" }, { "id": 2, "name": "say \"hi\" \\ ${x}" }]);
+        assert_eq!(yuck.render(&workspaces).unwrap(),
+            r#"(box (button :onclick "hyprctl dispatch workspace 1" "1") (button :onclick "hyprctl dispatch workspace 2" "say \"hi\" \\ \${x}"))"#);
+        assert_eq!(yuck.render(&json!(null)).unwrap(), "(box )");
+    }
+
+    #[test]
+    fn reports_invalid_templates() {
+        assert!(Template::new("{{#each this}}").is_err());
+    }
+}
@@ -1,40 +1,374 @@
-use std::env;
-use std::io::{Read, Write};
+use std::{env, fs};
+use std::io::{self, ErrorKind, Read, Write};
 use std::os::unix::net::UnixStream;
 use std::path::PathBuf;
+use std::sync::RwLock;
+use std::time::Duration;
 use anyhow::Context;
 use serde_json::Value;
 
 /// Opens socket two for reading
-pub fn open_events() -> anyhow::Result<UnixStream> {
+pub fn open_events() -> anyhow::Result<EventReader<UnixStream>> {
     UnixStream::connect(get_hypr_socket("socket2")?).context("failed to open socket 2")
+        .map(EventReader::new)
 }
 
-/// Reads a list of new events off of socket 2, returns an empty list if socket is closed
-pub fn read_events(socket: &mut UnixStream) -> anyhow::Result<Vec<(String, Vec<String>)>> {
-    // Yes, if there is data on the socket > than 256 bytes, things begin to break.
-    let mut buf = [0; 256];
+/// Reads events off of socket 2, keeping incomplete lines around until the rest of them arrives
+pub struct EventReader<R: Read> {
+    source: R,
+    buffer: Vec<u8>
+}
+
+impl<R: Read> EventReader<R> {
+    pub fn new(source: R) -> Self {
+        Self { source, buffer: vec![] }
+    }
+
+    /// Reads a list of new events, blocks until at least one complete event is available, returns an empty list if the socket is closed
+    pub fn read_events(&mut self) -> anyhow::Result<Vec<HyprEvent>> {
+        let mut buf = [0; 4096];
+
+        loop {
+            let len = self.source.read(&mut buf).context("failed to read from socket 2")?;
+            if len == 0 { return Ok(vec![]) }
+
+            self.buffer.extend_from_slice(&buf[..len]);
+
+            // Only consume up to and including the last newline, the rest is an incomplete event
+            let Some(end) = self.buffer.iter().rposition(|b| *b == b'\n') else { continue };
+            let lines: Vec<u8> = self.buffer.drain(..=end).collect();
+
+            let events: Vec<HyprEvent> = String::from_utf8(lines).context("socket 2 did not return valid utf-8")?
+                .split('\n').filter(|s| !s.is_empty()).map(HyprEvent::parse).collect();
+
+            if !events.is_empty() { return Ok(events) }
+        }
+    }
+}
+
+impl EventReader<UnixStream> {
+    /// Reads a list of new events like [`EventReader::read_events`], but returns none if no complete event arrived before the timeout
+    pub fn read_events_timeout(&mut self, timeout: Duration) -> anyhow::Result<Option<Vec<HyprEvent>>> {
+        self.source.set_read_timeout(Some(timeout)).context("failed to set timeout on socket 2")?;
+        let result = self.read_events();
+        self.source.set_read_timeout(None).context("failed to reset timeout on socket 2")?;
+
+        match result {
+            Ok(events) => { Ok(Some(events)) }
+            Err(e) if e.downcast_ref::<io::Error>().is_some_and(|e| matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut)) => { Ok(None) }
+            Err(e) => { Err(e) }
+        }
+    }
+}
+
+/// Event received from socket 2, see https://wiki.hyprland.org/IPC/#events-list for their meaning
+#[derive(Debug, Clone, PartialEq)]
+pub enum HyprEvent {
+    Workspace { name: String },
+    WorkspaceV2 { id: i64, name: String },
+    FocusedMon { monitor: String, workspace: String },
+    FocusedMonV2 { monitor: String, workspace_id: i64 },
+    ActiveWindow { class: String, title: String },
+    ActiveWindowV2 { address: String },
+    Fullscreen { enabled: bool },
+    MonitorRemoved { name: String },
+    MonitorRemovedV2 { id: i64, name: String, description: String },
+    MonitorAdded { name: String },
+    MonitorAddedV2 { id: i64, name: String, description: String },
+    CreateWorkspace { name: String },
+    CreateWorkspaceV2 { id: i64, name: String },
+    DestroyWorkspace { name: String },
+    DestroyWorkspaceV2 { id: i64, name: String },
+    MoveWorkspace { name: String, monitor: String },
+    MoveWorkspaceV2 { id: i64, name: String, monitor: String },
+    RenameWorkspace { id: i64, name: String },
+    ActiveSpecial { name: String, monitor: String },
+    /// The id and name are empty when the special workspace is closed
+    ActiveSpecialV2 { id: Option<i64>, name: String, monitor: String },
+    ActiveLayout { keyboard: String, layout: String },
+    OpenWindow { address: String, workspace: String, class: String, title: String },
+    CloseWindow { address: String },
+    MoveWindow { address: String, workspace: String },
+    MoveWindowV2 { address: String, workspace_id: i64, workspace: String },
+    OpenLayer { namespace: String },
+    CloseLayer { namespace: String },
+    Submap { name: String },
+    ChangeFloatingMode { address: String, floating: bool },
+    Urgent { address: String },
+    Minimize { address: String, minimized: bool },
+    Screencast { active: bool, owner: String },
+    WindowTitle { address: String },
+    WindowTitleV2 { address: String, title: String },
+    ToggleGroup { open: bool, addresses: Vec<String> },
+    MoveIntoGroup { address: String },
+    MoveOutOfGroup { address: String },
+    IgnoreGroupLock { enabled: bool },
+    LockGroups { locked: bool },
+    ConfigReloaded,
+    Pin { address: String, pinned: bool },
+    /// The address is empty if the bell does not belong to a window
+    Bell { address: String },
+    /// Event which is not known (yet) or could not be parsed
+    Unknown { name: String, args: Vec<String> },
+}
+
+impl HyprEvent {
+    /// Parses a single event line, e.g. activewindow>>alacritty,Window Title
+    pub fn parse(line: &str) -> Self {
+        let (name, data) = line.split_once(">>").unwrap_or((line, ""));
+
+        Self::parse_known(name, data).unwrap_or_else(|| {
+            let args = if data.is_empty() { vec![] } else { data.split(',').map(String::from).collect() };
+            HyprEvent::Unknown { name: name.to_string(), args }
+        })
+    }
+
+    /// Name of the event, as sent by hyprland
+    pub fn name(&self) -> &str {
+        use HyprEvent::*;
+
+        match self {
+            Workspace { .. } => "workspace",
+            WorkspaceV2 { .. } => "workspacev2",
+            FocusedMon { .. } => "focusedmon",
+            FocusedMonV2 { .. } => "focusedmonv2",
+            ActiveWindow { .. } => "activewindow",
+            ActiveWindowV2 { .. } => "activewindowv2",
+            Fullscreen { .. } => "fullscreen",
+            MonitorRemoved { .. } => "monitorremoved",
+            MonitorRemovedV2 { .. } => "monitorremovedv2",
+            MonitorAdded { .. } => "monitoradded",
+            MonitorAddedV2 { .. } => "monitoraddedv2",
+            CreateWorkspace { .. } => "createworkspace",
+            CreateWorkspaceV2 { .. } => "createworkspacev2",
+            DestroyWorkspace { .. } => "destroyworkspace",
+            DestroyWorkspaceV2 { .. } => "destroyworkspacev2",
+            MoveWorkspace { .. } => "moveworkspace",
+            MoveWorkspaceV2 { .. } => "moveworkspacev2",
+            RenameWorkspace { .. } => "renameworkspace",
+            ActiveSpecial { .. } => "activespecial",
+            ActiveSpecialV2 { .. } => "activespecialv2",
+            ActiveLayout { .. } => "activelayout",
+            OpenWindow { .. } => "openwindow",
+            CloseWindow { .. } => "closewindow",
+            MoveWindow { .. } => "movewindow",
+            MoveWindowV2 { .. } => "movewindowv2",
+            OpenLayer { .. } => "openlayer",
+            CloseLayer { .. } => "closelayer",
+            Submap { .. } => "submap",
+            ChangeFloatingMode { .. } => "changefloatingmode",
+            Urgent { .. } => "urgent",
+            Minimize { .. } => "minimize",
+            Screencast { .. } => "screencast",
+            WindowTitle { .. } => "windowtitle",
+            WindowTitleV2 { .. } => "windowtitlev2",
+            ToggleGroup { .. } => "togglegroup",
+            MoveIntoGroup { .. } => "moveintogroup",
+            MoveOutOfGroup { .. } => "moveoutofgroup",
+            IgnoreGroupLock { .. } => "ignoregrouplock",
+            LockGroups { .. } => "lockgroups",
+            ConfigReloaded => "configreloaded",
+            Pin { .. } => "pin",
+            Bell { .. } => "bell",
+            Unknown { name, .. } => name,
+        }
+    }
+
+    /// Arguments of the event in the order sent by hyprland, fields containing commas are kept intact
+    pub fn args(&self) -> Vec<String> {
+        use HyprEvent::*;
 
-    let len = socket.read(&mut buf).context("failed to read from socket 2")?;
+        let flag = |b: &bool| if *b { "1" } else { "0" }.to_string();
 
-    Ok(String::from_utf8(buf[..len].to_vec()).context("socket 2 did not return valid utf-8")?
-        .split('\n').filter(|s| !s.is_empty()).filter_map(|s| {
+        match self {
+            Workspace { name } | CreateWorkspace { name } | DestroyWorkspace { name } | MonitorRemoved { name } |
+            MonitorAdded { name } | Submap { name } => vec![name.clone()],
+            WorkspaceV2 { id, name } | CreateWorkspaceV2 { id, name } | DestroyWorkspaceV2 { id, name } |
+            RenameWorkspace { id, name } => vec![id.to_string(), name.clone()],
+            FocusedMon { monitor, workspace } => vec![monitor.clone(), workspace.clone()],
+            FocusedMonV2 { monitor, workspace_id } => vec![monitor.clone(), workspace_id.to_string()],
+            ActiveWindow { class, title } => vec![class.clone(), title.clone()],
+            ActiveWindowV2 { address } | CloseWindow { address } | Urgent { address } | WindowTitle { address } |
+            MoveIntoGroup { address } | MoveOutOfGroup { address } | Bell { address } => vec![address.clone()],
+            Fullscreen { enabled } | IgnoreGroupLock { enabled } => vec![flag(enabled)],
+            MonitorAddedV2 { id, name, description } | MonitorRemovedV2 { id, name, description } => vec![id.to_string(), name.clone(), description.clone()],
+            MoveWorkspace { name, monitor } | ActiveSpecial { name, monitor } => vec![name.clone(), monitor.clone()],
+            MoveWorkspaceV2 { id, name, monitor } => vec![id.to_string(), name.clone(), monitor.clone()],
+            ActiveSpecialV2 { id, name, monitor } => vec![id.map(|id| id.to_string()).unwrap_or_default(), name.clone(), monitor.clone()],
+            ActiveLayout { keyboard, layout } => vec![keyboard.clone(), layout.clone()],
+            OpenWindow { address, workspace, class, title } => vec![address.clone(), workspace.clone(), class.clone(), title.clone()],
+            MoveWindow { address, workspace } => vec![address.clone(), workspace.clone()],
+            MoveWindowV2 { address, workspace_id, workspace } => vec![address.clone(), workspace_id.to_string(), workspace.clone()],
+            OpenLayer { namespace } | CloseLayer { namespace } => vec![namespace.clone()],
+            ChangeFloatingMode { address, floating } => vec![address.clone(), flag(floating)],
+            Minimize { address, minimized } => vec![address.clone(), flag(minimized)],
+            Screencast { active, owner } => vec![flag(active), owner.clone()],
+            WindowTitleV2 { address, title } => vec![address.clone(), title.clone()],
+            ToggleGroup { open, addresses } => [vec![flag(open)], addresses.clone()].concat(),
+            LockGroups { locked } => vec![flag(locked)],
+            ConfigReloaded => vec![],
+            Pin { address, pinned } => vec![address.clone(), flag(pinned)],
+            Unknown { args, .. } => args.clone(),
+        }
+    }
+
+    /// Parses the data of events we know, returns none if it is malformed
+    fn parse_known(name: &str, data: &str) -> Option<Self> {
+        use HyprEvent::*;
+
+        let data = data.to_string();
+
+        Some(match name {
+            "workspace" => Workspace { name: data },
+            "workspacev2" => {
+                let [id, name] = fields(&data)?;
+                WorkspaceV2 { id: id.parse().ok()?, name }
+            }
+            "focusedmon" => {
+                let [monitor, workspace] = fields(&data)?;
+                FocusedMon { monitor, workspace }
+            }
+            "focusedmonv2" => {
+                let [monitor, id] = fields_rev(&data)?;
+                FocusedMonV2 { monitor, workspace_id: id.parse().ok()? }
+            }
+            "activewindow" => {
+                let [class, title] = fields(&data)?;
+                ActiveWindow { class, title }
+            }
+            "activewindowv2" => ActiveWindowV2 { address: data },
+            "fullscreen" => Fullscreen { enabled: flag(&data)? },
+            "monitorremoved" => MonitorRemoved { name: data },
+            "monitorremovedv2" => {
+                let [id, name, description] = fields(&data)?;
+                MonitorRemovedV2 { id: id.parse().ok()?, name, description }
+            }
+            "monitoradded" => MonitorAdded { name: data },
+            "monitoraddedv2" => {
+                let [id, name, description] = fields(&data)?;
+                MonitorAddedV2 { id: id.parse().ok()?, name, description }
+            }
+            "createworkspace" => CreateWorkspace { name: data },
+            "createworkspacev2" => {
+                let [id, name] = fields(&data)?;
+                CreateWorkspaceV2 { id: id.parse().ok()?, name }
+            }
+            "destroyworkspace" => DestroyWorkspace { name: data },
+            "destroyworkspacev2" => {
+                let [id, name] = fields(&data)?;
+                DestroyWorkspaceV2 { id: id.parse().ok()?, name }
+            }
+            "moveworkspace" => {
+                let [name, monitor] = fields_rev(&data)?;
+                MoveWorkspace { name, monitor }
+            }
+            "moveworkspacev2" => {
+                let [id, rest] = fields(&data)?;
+                let [name, monitor] = fields_rev(&rest)?;
+                MoveWorkspaceV2 { id: id.parse().ok()?, name, monitor }
+            }
+            "renameworkspace" => {
+                let [id, name] = fields(&data)?;
+                RenameWorkspace { id: id.parse().ok()?, name }
+            }
+            "activespecial" => {
+                let [name, monitor] = fields_rev(&data)?;
+                ActiveSpecial { name, monitor }
+            }
+            "activespecialv2" => {
+                let [id, rest] = fields(&data)?;
+                let [name, monitor] = fields_rev(&rest)?;
+                let id = if id.is_empty() { None } else { Some(id.parse().ok()?) };
+                ActiveSpecialV2 { id, name, monitor }
+            }
+            "activelayout" => {
+                let [keyboard, layout] = fields(&data)?;
+                ActiveLayout { keyboard, layout }
+            }
+            "openwindow" => {
+                let [address, workspace, class, title] = fields(&data)?;
+                OpenWindow { address, workspace, class, title }
+            }
+            "closewindow" => CloseWindow { address: data },
+            "movewindow" => {
+                let [address, workspace] = fields(&data)?;
+                MoveWindow { address, workspace }
+            }
+            "movewindowv2" => {
+                let [address, id, workspace] = fields(&data)?;
+                MoveWindowV2 { address, workspace_id: id.parse().ok()?, workspace }
+            }
+            "openlayer" => OpenLayer { namespace: data },
+            "closelayer" => CloseLayer { namespace: data },
+            "submap" => Submap { name: data },
+            "changefloatingmode" => {
+                let [address, floating] = fields(&data)?;
+                ChangeFloatingMode { address, floating: flag(&floating)? }
+            }
+            "urgent" => Urgent { address: data },
+            "minimize" => {
+                let [address, minimized] = fields(&data)?;
+                Minimize { address, minimized: flag(&minimized)? }
+            }
+            "screencast" => {
+                let [active, owner] = fields(&data)?;
+                Screencast { active: flag(&active)?, owner }
+            }
+            "windowtitle" => WindowTitle { address: data },
+            "windowtitlev2" => {
+                let [address, title] = fields(&data)?;
+                WindowTitleV2 { address, title }
+            }
+            "togglegroup" => {
+                let (open, addresses) = data.split_once(',').unwrap_or((&data, ""));
+                let addresses = addresses.split(',').filter(|s| !s.is_empty()).map(String::from).collect();
+                ToggleGroup { open: flag(open)?, addresses }
+            }
+            "moveintogroup" => MoveIntoGroup { address: data },
+            "moveoutofgroup" => MoveOutOfGroup { address: data },
+            "ignoregrouplock" => IgnoreGroupLock { enabled: flag(&data)? },
+            "lockgroups" => LockGroups { locked: flag(&data)? },
+            "configreloaded" => ConfigReloaded,
+            "pin" => {
+                let [address, pinned] = fields(&data)?;
+                Pin { address, pinned: flag(&pinned)? }
+            }
+            "bell" => Bell { address: data },
+            _ => return None
+        })
+    }
+}
 
-        // Parses e.g. activewindow>>alacritty,Window Title
-        let splits: Vec<&str> = s.split(">>").collect();
-        if splits.is_empty() { return None }
+/// Splits event data into exactly N fields, the last field keeps any further commas (e.g. in window titles)
+fn fields<const N: usize>(data: &str) -> Option<[String; N]> {
+    data.splitn(N, ',').map(String::from).collect::<Vec<String>>().try_into().ok()
+}
 
-        let name = splits.first().expect("vec behaving weirdly").to_string();
-        let args = if let Some(args) = splits.get(1) {
-            args.split(',').map(String::from).collect()
-        } else { vec![] };
+/// Splits event data into exactly N fields from the back, the first field keeps any further commas (e.g. in workspace names)
+fn fields_rev<const N: usize>(data: &str) -> Option<[String; N]> {
+    let mut fields: Vec<String> = data.rsplitn(N, ',').map(String::from).collect();
+    fields.reverse();
+    fields.try_into().ok()
+}
 
-        Some((name, args))
-    }).collect())
+/// Parses a boolean flag as sent by hyprland (0 or 1)
+fn flag(data: &str) -> Option<bool> {
+    match data {
+        "0" => Some(false),
+        "1" => Some(true),
+        _ => None
+    }
 }
 
 /// Gets information from socket 1, is always executed through a batch request and returned in json
 pub fn get_info(requests: Vec<String>) -> anyhow::Result<Vec<Value>>{
+    get_info_raw(requests)?.iter()
+        .map(|s| serde_json::from_str::<Value>(s))
+        .collect::<serde_json::Result<Vec<Value>>>().context("socket 1 did not return valid json")
+}
+
+/// Executes a batch request on socket 1 like [`get_info`], but returns the unparsed responses
+pub fn get_info_raw(requests: Vec<String>) -> anyhow::Result<Vec<String>> {
     // Produces request string, e.g. [[BATCH]] j/monitors ; j/workspaces
     let request = "[[BATCH]] ".to_string() + &requests.iter().map(|s| "j/".to_string() + s).collect::<Vec<String>>().join(" ; ");
 
@@ -45,21 +379,224 @@ pub fn get_info(requests: Vec<String>) -> anyhow::Result<Vec<Value>>{
     socket.read_to_string(&mut response).context("failed to read from socket 1")?;
 
     // Now split by that inserted character
-    response.split("\n\n\n")
-        .map(serde_json::from_str::<Value>)
-        .collect::<serde_json::Result<Vec<Value>>>().context("socket 1 did not return valid json")
+    Ok(response.split("\n\n\n").map(String::from).collect())
 }
 
+/// Signature of the instance which is used instead of the one of the environment, after hyprland was restarted under a new one
+static INSTANCE: RwLock<Option<String>> = RwLock::new(None);
+
 /// Returns the path to a socket, based on its name (without . and ending) and the instance signature
 pub fn get_hypr_socket(name: &str) -> anyhow::Result<String> {
-    let instance = env::var("HYPRLAND_INSTANCE_SIGNATURE").context("couldn't find instance singature, is hyprland running?")?;
+    let instance = match INSTANCE.read().ok().and_then(|i| i.clone()) {
+        Some(instance) => { instance }
+        None => { env::var("HYPRLAND_INSTANCE_SIGNATURE").context("couldn't find instance singature, is hyprland running?")? }
+    };
+
+    Ok(format!("{}/{instance}/.{name}.sock", socket_dir()?))
+}
+
+/// Switches both sockets to the most recently started hyprland instance, e.g. because hyprland crashed and was restarted under a new signature.
+/// Returns false if that is the instance which is already used, or there is none.
+pub fn use_newest_instance() -> bool {
+    let Some(newest) = socket_dir().ok().and_then(|dir| newest_instance(&dir)) else { return false };
+    if get_hypr_socket("socket2").is_ok_and(|socket| socket.ends_with(&format!("/{newest}/.socket2.sock"))) { return false }
+
+    match INSTANCE.write() {
+        Ok(mut instance) => { *instance = Some(newest); true }
+        Err(_) => { false }
+    }
+}
 
+/// Returns the directory containing the directories of all hyprland instances
+fn socket_dir() -> anyhow::Result<String> {
     // use runtime dir or tmp if that doesn't exist
-    let mut dir = env::var("XDG_RUNTIME_DIR").context("couldn't get current runtime dir, are you running as a user?")? + "/hypr";
+    let dir = env::var("XDG_RUNTIME_DIR").context("couldn't get current runtime dir, are you running as a user?")? + "/hypr";
     if !PathBuf::from(&dir).exists() {
-        dir = "/tmp/hypr".to_string();
+        return Ok("/tmp/hypr".to_string());
     }
 
-    Ok(format!("{dir}/{instance}/.{name}.sock"))
+    Ok(dir)
+}
+
+/// Finds the signature of the most recently started hyprland instance in the directory, based on its event socket
+fn newest_instance(dir: &str) -> Option<String> {
+    fs::read_dir(dir).ok()?
+        .filter_map(Result::ok)
+        .filter_map(|entry| Some((entry.path().join(".socket2.sock").metadata().and_then(|m| m.modified()).ok()?, entry)))
+        .max_by_key(|(modified, _)| *modified)
+        .and_then(|(_, entry)| entry.file_name().to_str().map(String::from))
 }
 
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::io::Read;
+    use super::{newest_instance, EventReader, HyprEvent};
+
+    /// Returns the given chunks one read at a time, like a socket would
+    struct Chunks(VecDeque<Vec<u8>>);
+
+    impl Chunks {
+        fn new(chunks: &[&[u8]]) -> Self {
+            Self(chunks.iter().map(|c| c.to_vec()).collect())
+        }
+    }
+
+    impl Read for Chunks {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let Some(mut chunk) = self.0.pop_front() else { return Ok(0) };
+
+            let len = chunk.len().min(buf.len());
+            buf[..len].copy_from_slice(&chunk[..len]);
+
+            // Keep what didn't fit for the next read
+            if len < chunk.len() {
+                self.0.push_front(chunk.split_off(len));
+            }
+
+            Ok(len)
+        }
+    }
+
+    fn focusedmon() -> HyprEvent {
+        HyprEvent::FocusedMon { monitor: "DP-1".into(), workspace: "2".into() }
+    }
+
+    fn workspace() -> HyprEvent {
+        HyprEvent::Workspace { name: "2".into() }
+    }
+
+    #[test]
+    fn reads_single_event() {
+        let mut reader = EventReader::new(Chunks::new(&[b"workspace>>2\n"]));
+
+        assert_eq!(reader.read_events().unwrap(), vec![workspace()]);
+        assert!(reader.read_events().unwrap().is_empty());
+    }
+
+    #[test]
+    fn reads_merged_events() {
+        let mut reader = EventReader::new(Chunks::new(&[b"workspace>>2\nfocusedmon>>DP-1,2\nactivewindowv2>>5612f0a8c0\n"]));
+
+        assert_eq!(reader.read_events().unwrap(), vec![
+            workspace(),
+            focusedmon(),
+            HyprEvent::ActiveWindowV2 { address: "5612f0a8c0".into() },
+        ]);
+    }
+
+    #[test]
+    fn keeps_split_events() {
+        let mut reader = EventReader::new(Chunks::new(&[b"workspace>>2\nfocused", b"mon>>DP-1", b",2\nconfigreloaded>>\n"]));
+
+        assert_eq!(reader.read_events().unwrap(), vec![workspace()]);
+        assert_eq!(reader.read_events().unwrap(), vec![focusedmon(), HyprEvent::ConfigReloaded]);
+    }
+
+    #[test]
+    fn reads_events_larger_than_buffer() {
+        let title = "a".repeat(10000);
+        let line = format!("activewindow>>alacritty,{title}\n");
+        let (first, second) = line.as_bytes().split_at(5000);
+
+        let mut reader = EventReader::new(Chunks::new(&[first, second]));
+
+        assert_eq!(reader.read_events().unwrap(), vec![HyprEvent::ActiveWindow { class: "alacritty".into(), title }]);
+    }
+
+    #[test]
+    fn keeps_split_utf8() {
+        let line = "windowtitlev2>>5612f0a8c0,ümlaut\n".as_bytes();
+        let (first, second) = line.split_at(27);
+
+        let mut reader = EventReader::new(Chunks::new(&[first, second]));
+
+        assert_eq!(reader.read_events().unwrap(), vec![HyprEvent::WindowTitleV2 { address: "5612f0a8c0".into(), title: "ümlaut".into() }]);
+    }
+
+    #[test]
+    fn discards_incomplete_event_on_close() {
+        let mut reader = EventReader::new(Chunks::new(&[b"workspace>>2\nworksp"]));
+
+        assert_eq!(reader.read_events().unwrap(), vec![workspace()]);
+        assert!(reader.read_events().unwrap().is_empty());
+    }
+
+    #[test]
+    fn parses_titles_with_commas() {
+        assert_eq!(HyprEvent::parse("openwindow>>5612f0a8c0,3,firefox,Hello, World - Mozilla Firefox"), HyprEvent::OpenWindow {
+            address: "5612f0a8c0".into(),
+            workspace: "3".into(),
+            class: "firefox".into(),
+            title: "Hello, World - Mozilla Firefox".into()
+        });
+        assert_eq!(HyprEvent::parse("moveworkspacev2>>4,work, stuff,DP-1"), HyprEvent::MoveWorkspaceV2 {
+            id: 4,
+            name: "work, stuff".into(),
+            monitor: "DP-1".into()
+        });
+    }
+
+    #[test]
+    fn parses_empty_arguments() {
+        assert_eq!(HyprEvent::parse("submap>>"), HyprEvent::Submap { name: "".into() });
+        assert_eq!(HyprEvent::parse("togglegroup>>0,"), HyprEvent::ToggleGroup { open: false, addresses: vec![] });
+        assert_eq!(HyprEvent::parse("togglegroup>>1,5612f0a8c0,5612f0b9d0"), HyprEvent::ToggleGroup {
+            open: true,
+            addresses: vec!["5612f0a8c0".into(), "5612f0b9d0".into()]
+        });
+    }
+
+    #[test]
+    fn returns_name_and_args() {
+        let event = HyprEvent::parse("openwindow>>5612f0a8c0,3,firefox,Hello, World");
+
+        assert_eq!(event.name(), "openwindow");
+        assert_eq!(event.args(), vec!["5612f0a8c0", "3", "firefox", "Hello, World"]);
+        assert_eq!(HyprEvent::parse("someday>>a,b").name(), "someday");
+        assert_eq!(HyprEvent::parse("changefloatingmode>>5612f0a8c0,1").args(), vec!["5612f0a8c0", "1"]);
+    }
+
+    #[test]
+    fn parses_v2_events() {
+        assert_eq!(HyprEvent::parse("focusedmonv2>>DP-1,3"), HyprEvent::FocusedMonV2 { monitor: "DP-1".into(), workspace_id: 3 });
+        assert_eq!(HyprEvent::parse("monitorremovedv2>>1,HDMI-A-1,LG Electronics, 27GL850"), HyprEvent::MonitorRemovedV2 {
+            id: 1,
+            name: "HDMI-A-1".into(),
+            description: "LG Electronics, 27GL850".into(),
+        });
+        assert_eq!(HyprEvent::parse("activespecialv2>>-98,special:magic,DP-1"), HyprEvent::ActiveSpecialV2 {
+            id: Some(-98),
+            name: "special:magic".into(),
+            monitor: "DP-1".into(),
+        });
+        assert_eq!(HyprEvent::parse("activespecialv2>>,,DP-1").args(), vec!["", "", "DP-1"]);
+        assert_eq!(HyprEvent::parse("bell>>"), HyprEvent::Bell { address: "".into() });
+    }
+
+    #[test]
+    fn falls_back_to_unknown() {
+        assert_eq!(HyprEvent::parse("someday>>a,b"), HyprEvent::Unknown { name: "someday".into(), args: vec!["a".into(), "b".into()] });
+        assert_eq!(HyprEvent::parse("workspacev2>>notanumber,2"), HyprEvent::Unknown { name: "workspacev2".into(), args: vec!["notanumber".into(), "2".into()] });
+        assert_eq!(HyprEvent::parse("fullscreen>>"), HyprEvent::Unknown { name: "fullscreen".into(), args: vec![] });
+    }
+
+    #[test]
+    fn finds_newest_instance() {
+        let dir = std::env::temp_dir().join(format!("hyprwatch-instances-{}", std::process::id()));
+
+        for (instance, age) in [("old", 60), ("new", 0), ("newer-without-socket", 0)] {
+            std::fs::create_dir_all(dir.join(instance)).unwrap();
+            if instance.ends_with("without-socket") { continue }
+
+            let socket = std::fs::File::create(dir.join(instance).join(".socket2.sock")).unwrap();
+            socket.set_modified(std::time::SystemTime::now() - std::time::Duration::from_secs(age)).unwrap();
+        }
+
+        let newest = newest_instance(dir.to_str().unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(newest.as_deref(), Some("new"));
+        assert_eq!(newest_instance("/nonexistent/hypr"), None);
+    }
+}
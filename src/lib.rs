@@ -0,0 +1,21 @@
+//! Library behind the *hyprwatch* CLI, which can be used to watch hyprland's state from other programs.
+//!
+//! - [`hypr`] contains the raw socket clients for socket 1 (queries) and socket 2 (events).
+//! - [`model`] contains typed models of the entities returned by hyprland.
+//! - [`prepare`] retrieves entities from hyprland and enriches them with additional attributes.
+//! - [`state`] caches responses of socket 1 and applies cheap events to them directly.
+//! - [`watch`] combines the above to watch entities for changes.
+//! - [`filter`] filters the data with expressions.
+//! - [`project`] reduces the data to selected fields.
+//! - [`format`] renders the data as text with templates.
+//! - [`diff`] computes JSON Patches between two versions of the data.
+
+pub mod diff;
+pub mod filter;
+pub mod format;
+pub mod hypr;
+pub mod model;
+pub mod prepare;
+pub mod project;
+pub mod state;
+pub mod watch;
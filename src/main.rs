@@ -1,18 +1,13 @@
-#![feature(try_blocks)]
-
-mod hypr;
-
-use std::collections::HashMap;
 use std::process::exit;
-use std::str::FromStr;
-use anyhow::{Context};
-use clap::{Parser, Subcommand};
-use serde_json::{to_string, to_string_pretty, Value};
-use crate::hypr::{get_info, open_events, read_events};
-
-const MONITOR_EVENTS: [&str; 3] = ["focusedmon", "monitorremoved", "monitoradded"];
-const WORKSPACE_EVENTS: [&str; 11] = ["focusedmon", "monitorremoved", "monitoradded", "workspace", "createworkspace", "destroyworkspace", "moveworkspace", "openwindow", "closewindow", "movewindow", "activespecial"];
-const CLIENT_EVENTS: [&str; 7] = ["openwindow", "closewindow", "movewindow", "changefloatingmode", "fullscreen", "windowtitle", "activewindowv2"];
+use std::time::{SystemTime, UNIX_EPOCH};
+use anyhow::{anyhow, Context};
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand};
+use serde_json::{json, to_string, to_string_pretty, Value};
+use hyprwatch::diff::diff;
+use hyprwatch::format::{Template, WaybarOptions, YuckOptions};
+use hyprwatch::hypr::{open_events, HyprEvent};
+use hyprwatch::watch::{watch, Target, Update, WatchOptions};
 
 #[derive(Parser)]
 #[clap(version, about)]
@@ -27,230 +22,163 @@ struct Command {
     /// Pretty print result (uses multiple lines per event)
     #[clap(short, long)]
     pub pretty: bool,
+    /// Print every record rendered with this handlebars template instead of as json (e.g. "{{#each this}}{{name}} {{/each}}")
+    #[clap(short, long, value_parser = Template::new)]
+    pub format: Option<Template>,
+    /// Print only the changes since the last record, as JSON Patch operations (the first record replaces the whole document)
+    #[clap(long, conflicts_with_all = ["format", "waybar", "yuck"])]
+    pub diff: bool,
+    /// Print the events which caused the data to change alongside it, as {"events":[...],"data":...}
+    #[clap(short = 'e', long)]
+    pub with_events: bool,
+    /// Print status records (e.g. {"status":"disconnected"}) when the connection to hyprland changes
+    #[clap(short, long)]
+    pub status: bool,
+    #[command(flatten)]
+    pub waybar: WaybarOptions,
+    #[command(flatten)]
+    pub yuck: YuckOptions,
+    #[command(flatten)]
+    pub options: WatchOptions,
 }
 
 #[derive(Subcommand)]
 enum Type {
-    /// Watch changes in monitors
-    Monitors,
-    /// Watch changes in workspaces
-    Workspaces {
-        /// Only watch workspaces on monitor
-        #[clap(short, long)]
-        monitor: Option<String>,
-
-        /// Only watch for workspaces with special status
-        #[clap(short, long)]
-        special: Option<bool>,
-    },
-    /// Watch changes in clients (windows)
-    Clients {
-        /// Only watch clients on monitor
-        #[clap(short, long)]
-        monitor: Option<String>,
-        /// Only watch clients on workspace
-        #[clap(short, long)]
-        workspace: Option<String>,
+    #[command(flatten)]
+    Watch(Target),
+    /// Print the raw events of hyprland as they arrive
+    Events {
+        /// Only print events with these names (comma separated)
+        #[clap(short, long, value_delimiter = ',')]
+        include: Vec<String>,
+        /// Don't print events with these names (comma separated)
+        #[clap(short = 'x', long, value_delimiter = ',')]
+        exclude: Vec<String>,
     },
 }
 
 fn main() {
     let command = Command::parse();
 
-    // Eventless
-    print_data(&command);
-
-    if command.once { return; }
-
-    // Listen
-    let mut socket = match open_events() {
-        Ok(s) => { s }
-        Err(e) => {
-            eprintln!("{e}");
-            exit(-1);
+    let target = match &command.what {
+        Type::Watch(target) => { target }
+        Type::Events { include, exclude } => {
+            reject_data_options(&command);
+            if let Err(e) = print_events(&command, include, exclude) {
+                eprintln!("{e:#}");
+                exit(-1);
+            }
+            return;
         }
     };
 
-    let keywords = match &command.what {
-        Type::Monitors => { MONITOR_EVENTS.as_slice() }
-        Type::Workspaces { .. } => { WORKSPACE_EVENTS.as_slice() }
-        Type::Clients { .. } => { CLIENT_EVENTS.as_slice() }
-    };
-
-    loop {
-        let events = match read_events(&mut socket) {
-            Ok(e) => {
-                if e.is_empty() {
-                    eprintln!("hyprland event socket has closed");
-                    exit(-1);
-                } else { e }
-            }
-            Err(e) => {
-                eprintln!("{e}");
-                continue;
-            }
-        };
+    // Last printed data, which the diff is based on
+    let mut previous = None;
 
-        for (s, _) in events {
-            if keywords.contains(&s.as_str()) {
-                print_data(&command);
-                break;
-            }
-        }
+    // Eventless
+    if command.once {
+        print_data(&command, target.query().map(|data| target.shape(data, &command.options)), &[], &mut previous);
+        return;
     }
-}
 
-fn print_data(command: &Command) {
-    // retrieve data
-    let result = match &command.what {
-        Type::Monitors => { prepare_monitors() }
-        Type::Workspaces { monitor, special, .. } => { prepare_workspaces(monitor, special) }
-        Type::Clients { monitor, workspace } => { prepare_clients(monitor, workspace) }
-    };
-
-    // turn to string
-    let result = result.and_then(|v| {
-        if command.pretty { to_string_pretty(&v) } else { to_string(&v) }
-            .context("failed to serialize json")
+    // Listen
+    let result = watch(target.clone(), command.options.clone(), |update| {
+        match update {
+            Ok(Update::Data { data, events }) => { print_data(&command, Ok(data), &events, &mut previous) }
+            Ok(Update::Disconnected) => { print_status(&command, "disconnected") }
+            Ok(Update::Reconnected) => { print_status(&command, "connected") }
+            Err(e) => { eprintln!("{e:#}") }
+        }
     });
 
-    // print
-    match result {
-        Ok(s) => { println!("{s}") }
-        Err(e) => { eprintln!("{e}") }
+    if let Err(e) = result {
+        eprintln!("{e:#}");
+        exit(-1);
     }
 }
 
-/// Prepares the monitor data
-fn prepare_monitors() -> anyhow::Result<Value> {
-    let mut data = get_info(vec!("monitors".into()))?;
-
-    Ok(data.pop().expect("socket one seems broken"))
+/// Exits with a usage error if options which only apply to watched data are given with the events
+fn reject_data_options(command: &Command) {
+    let given = [
+        ("--filter", command.options.filter.is_some()),
+        ("--fields", !command.options.fields.is_empty()),
+        ("--diff", command.diff),
+        ("--with-events", command.with_events),
+    ];
+
+    if let Some((name, _)) = given.iter().find(|(_, given)| *given) {
+        Command::command().error(ErrorKind::ArgumentConflict, format!("the argument '{name}' cannot be used with 'events'")).exit();
+    }
 }
 
-/// Prepares the workspace data
-/// In addition to the workspaces, it will also query the monitor data to see whether the workspace is displayed and focussed
-fn prepare_workspaces(on_monitor: &Option<String>, special_status: &Option<bool>) -> anyhow::Result<Value> {
-    let mut data = get_info(vec!("workspaces".into(), "monitors".into()))?;
+/// Prints every event as it arrives, until the socket closes (or after the first one if only run once)
+fn print_events(command: &Command, include: &[String], exclude: &[String]) -> anyhow::Result<()> {
+    let mut socket = open_events()?;
 
-    // Process monitors to retrieve shown and active workspaces
-    let shown_map: Option<HashMap<i64, bool>> = try {
-        let mut map = HashMap::new();
-
-        let monitors = data.get(1)?;
-        for monitor in monitors.as_array()? {
-
-            // Special workspace
-            let special = monitor.get("specialWorkspace").and_then(|p| p.get("id")).and_then(Value::as_i64)?;
-            if special != 0 {
-                map.insert(special, true);
-            }
-
-            // Normal workspace
-            map.insert(
-                monitor.get("activeWorkspace").and_then(|p| p.get("id")).and_then(Value::as_i64)?,
-                special == 0 && monitor.get("focused").and_then(Value::as_bool)?);
-        }
-
-        map
-    };
-    let shown_map = shown_map.context("failure whilst reading monitors to find shown workspaces")?;
+    loop {
+        let events = socket.read_events()?;
+        if events.is_empty() { return Err(anyhow!("hyprland event socket has closed")) }
 
-    let mut body = data.remove(0);
-    if let Value::Array(workspaces) = &mut body {
-        // Remove workspaces not on monitor
-        if let Some(monitor) = &on_monitor {
-            workspaces.retain(|w| {
-                w.get("monitor").and_then(Value::as_str) == Some(monitor)
-            })
-        }
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or_default();
 
-        // Remove workspaces with special status
-        if let Some(special) = &special_status {
-            workspaces.retain(|w| {
-                w.get("id").and_then(Value::as_i64).map(|i| i < 0) == Some(*special)
-            })
-        }
+        for event in events {
+            let name = event.name().to_string();
+            if !include.is_empty() && !include.contains(&name) { continue }
+            if exclude.contains(&name) { continue }
 
-        // Add custom attributes
-        for workspace in workspaces.iter_mut() {
-            if let Value::Object(map) = workspace {
-                let info: Option<(i64, String)> = try {
-                    (map.get("id").and_then(Value::as_i64)?, map.get("name").and_then(Value::as_str)?.to_owned())
-                };
-                let (id, name) = info.context("failure whilst reading id and name of workspace")?;
+            print_json(command, Ok(json!({ "name": name, "args": event.args(), "timestamp": timestamp })));
 
-                map.insert("shown".into(), Value::Bool(shown_map.get(&id).is_some()));
-                map.insert("active".into(), Value::Bool(*shown_map.get(&id).unwrap_or(&false)));
-            }
+            if command.once { return Ok(()) }
         }
-
-        workspaces.sort_by(|w1, w2| {
-            w1.get("id").and_then(|v| v.as_i64()).unwrap_or(i64::MAX)
-                .cmp(&w2.get("id").and_then(|v| v.as_i64()).unwrap_or(i64::MAX))
-        })
     }
-
-    Ok(body)
 }
 
-/// Prepares the client data
-fn prepare_clients(monitor: &Option<String>, workspace: &Option<String>) -> anyhow::Result<Value> {
-    let mut data = get_info(vec!("clients".into(), "monitors".into()))?;
-
-    // map monitor ids to names
-    let monitor_names: Option<HashMap<u64, String>> = try {
-        let mut map = HashMap::new();
+fn print_data(command: &Command, data: anyhow::Result<Value>, events: &[HyprEvent], previous: &mut Option<Value>) {
+    let data = data.map(|data| {
+        let data = if command.diff { patch(previous, data) } else { data };
+        if command.with_events { envelope(data, events) } else { data }
+    });
 
-        let monitors = data.get(1).expect("socket one seems broken");
-        for monitor in monitors.as_array()? {
-            map.insert(monitor.get("id").and_then(Value::as_u64)?,
-                       monitor.get("name").and_then(Value::as_str)?.to_string());
-        }
+    print_json(command, data);
+}
 
-        map
-    };
-    let monitor_names = monitor_names.context("failure whilst reading monitor names for filtering")?;
+fn print_json(command: &Command, data: anyhow::Result<Value>) {
+    // turn to string
+    let result = data.and_then(|v| {
+        if let Some(template) = &command.format { return template.render(&v) }
+        if command.waybar.enabled { return command.waybar.render(&v).map(|v| v.to_string()) }
+        if command.yuck.yuck.is_some() { return command.yuck.render(&v) }
 
-    let mut body = data.remove(0);
-    if let Value::Array(clients) = &mut body {
-        // filter by workspace
-        if let Some(workspace) = workspace {
-            clients.retain(|c| {
-                let ws: Option<(&str, i64)> = try {
-                    let ws = c.get("workspace")?;
-                    (ws.get("name").and_then(Value::as_str)?, ws.get("id").and_then(Value::as_i64)?)
-                };
+        if command.pretty { to_string_pretty(&v) } else { to_string(&v) }
+            .context("failed to serialize json")
+    });
 
-                if let Some((name, id)) = ws {
-                    if let Some(desired_name) = workspace.strip_prefix("name:") {
-                        desired_name == name
-                    } else {
-                        i64::from_str(workspace).map(|desired_id| desired_id == id).unwrap_or_default()
-                    }
-                } else { false }
-            });
-        }
+    // print
+    match result {
+        Ok(s) => { println!("{s}") }
+        Err(e) => { eprintln!("{e:#}") }
+    }
+}
 
-        // associate and filter with monitors
-        clients.retain_mut(|c| {
-            if let Value::Object(map) = c {
-                if let Some(m) = map.get("monitor").and_then(Value::as_i64) {
-                    if let Some(name) = monitor_names.get(&(m as u64)) {
-                        map.insert("monitorName".to_string(), Value::String(name.clone()));
+/// Returns the operations changing the previous into the new data, which becomes the previous data afterwards
+fn patch(previous: &mut Option<Value>, data: Value) -> Value {
+    let operations = diff(previous.as_ref().unwrap_or(&Value::Null), &data);
+    *previous = Some(data);
 
-                        return if let Some(filter) = monitor {
-                            filter == name
-                        } else { true }
-                    }
-                }
+    Value::Array(operations)
+}
 
-                // still keep fails when no monitor filter is active
-                monitor.is_none()
-            } else { true }
-        })
-    }
+/// Wraps the data in an object together with the events which caused it
+fn envelope(data: Value, events: &[HyprEvent]) -> Value {
+    let events: Vec<Value> = events.iter().map(|e| json!({ "name": e.name(), "args": e.args() })).collect();
 
-    Ok(body)
+    json!({ "events": events, "data": data })
 }
 
+fn print_status(command: &Command, status: &str) {
+    if command.status {
+        print_json(command, Ok(json!({ "status": status })));
+    } else {
+        eprintln!("hyprland event socket is {status}");
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Reference to a workspace, as used inside monitors and clients
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct WorkspaceRef {
+    pub id: i64,
+    pub name: String,
+}
+
+/// Identity of a monitor as returned by `j/monitors`, for targets which only look up monitor names
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MonitorRef {
+    pub id: i64,
+    pub name: String,
+}
+
+/// Monitor as returned by `j/monitors`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Monitor {
+    pub id: i64,
+    pub name: String,
+    pub focused: bool,
+    pub active_workspace: WorkspaceRef,
+    pub special_workspace: WorkspaceRef,
+
+    /// Attributes which are not used by hyprwatch
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Monitor {
+    /// Returns the ids of the workspaces shown on this monitor, and whether they are also focused
+    pub fn shown_workspaces(&self) -> Vec<(i64, bool)> {
+        // Special workspace is shown on top of the normal one, and takes the focus
+        if self.special_workspace.id != 0 {
+            vec![(self.special_workspace.id, true), (self.active_workspace.id, false)]
+        } else {
+            vec![(self.active_workspace.id, self.focused)]
+        }
+    }
+}
+
+/// Workspace as returned by `j/workspaces`, with additional attributes
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Workspace {
+    pub id: i64,
+    pub name: String,
+    pub monitor: String,
+
+    /// Is the workspace currently shown on its monitor
+    #[serde(default)]
+    pub shown: bool,
+    /// Is the workspace shown and focused
+    #[serde(default)]
+    pub active: bool,
+    /// Does the workspace exist, or is it a persistent placeholder
+    #[serde(default = "existing")]
+    pub exists: bool,
+    /// Summaries of the clients on the workspace, if requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clients: Option<Vec<ClientSummary>>,
+
+    /// Attributes which are not used by hyprwatch
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Workspaces returned by hyprland always exist
+fn existing() -> bool { true }
+
+impl Workspace {
+    /// Creates a placeholder for a workspace which does not exist (yet)
+    pub fn placeholder(id: i64, monitor: String) -> Self {
+        let mut extra = Map::new();
+        extra.insert("windows".into(), Value::from(0));
+
+        Self { id, name: id.to_string(), monitor, shown: false, active: false, exists: false, clients: None, extra }
+    }
+
+    /// Whether this is a special workspace
+    pub fn is_special(&self) -> bool {
+        self.id < 0
+    }
+
+    /// Adds the shown and active attributes, based on the shown workspaces of all monitors
+    pub fn enrich(&mut self, shown: &HashMap<i64, bool>) {
+        self.shown = shown.contains_key(&self.id);
+        self.active = *shown.get(&self.id).unwrap_or(&false);
+    }
+}
+
+/// Summary of a client, as attached to the workspace it is on
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ClientSummary {
+    pub address: String,
+    pub class: String,
+    pub title: String,
+    /// Is the client the focused window
+    pub focused: bool,
+}
+
+/// Client as returned by `j/clients`, with additional attributes
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Client {
+    pub address: String,
+    pub monitor: i64,
+    pub workspace: WorkspaceRef,
+
+    /// Name of the monitor the client is on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monitor_name: Option<String>,
+
+    /// Attributes which are not used by hyprwatch
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Client {
+    /// Whether the client is on the given workspace, which is either an id or `name:` followed by its name
+    pub fn is_on_workspace(&self, workspace: &str) -> bool {
+        if let Some(name) = workspace.strip_prefix("name:") {
+            self.workspace.name == name
+        } else {
+            i64::from_str(workspace).map(|id| id == self.workspace.id).unwrap_or_default()
+        }
+    }
+
+    /// Adds the monitor name attribute, based on a map of monitor ids to names
+    pub fn enrich(&mut self, monitor_names: &HashMap<i64, String>) {
+        self.monitor_name = monitor_names.get(&self.monitor).cloned();
+    }
+
+    /// Summarizes the client to be attached to its workspace, it is focused if it is first in the focus history
+    pub fn summary(&self) -> ClientSummary {
+        let text = |field: &str| self.extra.get(field).and_then(Value::as_str).unwrap_or_default().to_string();
+
+        ClientSummary {
+            address: self.address.clone(),
+            class: text("class"),
+            title: text("title"),
+            focused: self.extra.get("focusHistoryID").and_then(Value::as_i64) == Some(0),
+        }
+    }
+}
+
+/// Layers of a monitor as returned by `j/layers`, which maps monitor names to these
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MonitorLayers {
+    /// Layer surfaces for every level, by the level's number
+    pub levels: BTreeMap<String, Vec<Layer>>,
+}
+
+/// Layer-shell surface as contained in `j/layers`, with additional attributes
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Layer {
+    pub address: String,
+    pub namespace: String,
+
+    /// Id of the monitor the layer is on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monitor: Option<i64>,
+    /// Name of the monitor the layer is on
+    #[serde(default)]
+    pub monitor_name: String,
+    /// Name of the level the layer is on (background, bottom, top or overlay)
+    #[serde(default)]
+    pub level: String,
+
+    /// Attributes which are not used by hyprwatch
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Layer {
+    /// Returns the name of a level, based on its number
+    fn level_name(level: &str) -> String {
+        match level {
+            "0" => "background",
+            "1" => "bottom",
+            "2" => "top",
+            "3" => "overlay",
+            other => other
+        }.to_string()
+    }
+
+    /// Adds the monitor and level attributes
+    pub fn enrich(&mut self, monitor_name: &str, level: &str, monitor_ids: &HashMap<String, i64>) {
+        self.monitor = monitor_ids.get(monitor_name).copied();
+        self.monitor_name = monitor_name.to_string();
+        self.level = Layer::level_name(level);
+    }
+}
+
+/// Input devices as returned by `j/devices`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Devices {
+    pub keyboards: Vec<Keyboard>,
+
+    /// Other kinds of devices (mice, tablets, etc.), which are not used by hyprwatch
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Devices {
+    /// Removes all devices which do not have the given name
+    pub fn retain_named(&mut self, name: &str) {
+        self.keyboards.retain(|k| k.name == name);
+
+        for devices in self.extra.values_mut().filter_map(Value::as_array_mut) {
+            devices.retain(|d| d.get("name").and_then(Value::as_str) == Some(name));
+        }
+    }
+}
+
+/// Keyboard as contained in `j/devices`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Keyboard {
+    pub address: String,
+    pub name: String,
+    /// Name of the currently active layout
+    pub active_keymap: String,
+    /// Whether this is the main keyboard, only reported by newer hyprland versions
+    #[serde(default)]
+    pub main: bool,
+
+    /// Attributes which are not used by hyprwatch
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
@@ -0,0 +1,473 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use anyhow::Context;
+use clap::{Args, ValueEnum};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use crate::hypr::get_info_raw;
+use crate::model::{Client, Devices, Monitor, MonitorLayers, MonitorRef, Workspace};
+
+/// Responses of socket 1 by the name of their request, none if the response was not valid json
+pub type Responses = HashMap<String, Option<Value>>;
+
+/// Queries all requests from socket 1 in a single batch
+pub fn query(requests: &[&str]) -> anyhow::Result<Responses> {
+    let responses = get_info_raw(requests.iter().map(|s| s.to_string()).collect())?;
+
+    Ok(requests.iter().zip(responses).map(|(request, response)| (request.to_string(), parse_response(request, &response))).collect())
+}
+
+/// Parses a response of socket 1 on its own, so responses which are not valid json (e.g. for requests unknown to older versions) don't fail the whole batch
+/// The submap is answered as `{"name"}`, which is not valid json, so its name is read as a string instead
+fn parse_response(request: &str, response: &str) -> Option<Value> {
+    if request == "submap" {
+        let name = response.trim().strip_prefix('{')?.strip_suffix('}')?;
+        return serde_json::from_str::<String>(name).ok().map(Value::String);
+    }
+
+    serde_json::from_str(response).ok()
+}
+
+/// Prepares the monitor data
+pub fn prepare_monitors(responses: &Responses) -> anyhow::Result<Value> {
+    let monitors: Vec<Monitor> = parse(responses, "monitors")?;
+
+    serde_json::to_value(monitors).context("failed to serialize monitors")
+}
+
+/// Prepares the workspace data
+/// In addition to the workspaces, it will also use the monitor data to see whether the workspace is displayed and focussed
+/// With clients, it also attaches summaries of the clients on every workspace, which requires the `clients` response
+pub fn prepare_workspaces(responses: &Responses, on_monitor: &Option<String>, special_status: &Option<bool>, persistent: &[Persistent], with_clients: bool) -> anyhow::Result<Value> {
+    // Process monitors to retrieve shown and active workspaces
+    let monitors: Vec<Monitor> = parse(responses, "monitors")?;
+    let shown_map: HashMap<i64, bool> = monitors.iter().flat_map(Monitor::shown_workspaces).collect();
+
+    let mut workspaces: Vec<Workspace> = parse(responses, "workspaces")?;
+
+    // Add placeholders for persistent workspaces, those without a monitor are on the filtered or first monitor, so they don't move with the focus
+    let fallback = on_monitor.clone().or_else(|| monitors.iter().min_by_key(|m| m.id).map(|m| m.name.clone())).unwrap_or_default();
+    for persistent in persistent {
+        for id in &persistent.ids {
+            if workspaces.iter().any(|w| w.id == *id) { continue }
+
+            workspaces.push(Workspace::placeholder(*id, persistent.monitor.clone().unwrap_or_else(|| fallback.clone())));
+        }
+    }
+
+    // Remove workspaces not on monitor
+    if let Some(monitor) = &on_monitor {
+        workspaces.retain(|w| &w.monitor == monitor)
+    }
+
+    // Remove workspaces with special status
+    if let Some(special) = &special_status {
+        workspaces.retain(|w| w.is_special() == *special)
+    }
+
+    // Add custom attributes
+    for workspace in workspaces.iter_mut() {
+        workspace.enrich(&shown_map);
+    }
+
+    if with_clients {
+        let clients: Vec<Client> = parse(responses, "clients")?;
+
+        for workspace in workspaces.iter_mut() {
+            workspace.clients = Some(clients.iter().filter(|c| c.workspace.id == workspace.id).map(Client::summary).collect());
+        }
+    }
+
+    workspaces.sort_by_key(|w| w.id);
+
+    serde_json::to_value(workspaces).context("failed to serialize workspaces")
+}
+
+/// Workspaces which are always included, even if they don't exist, optionally on a specific monitor
+#[derive(Clone, Debug, PartialEq)]
+pub struct Persistent {
+    pub monitor: Option<String>,
+    pub ids: Vec<i64>,
+}
+
+impl Persistent {
+    /// Parses a list of workspace ids and ranges, optionally prefixed by a monitor, like `1-5,8` or `DP-1:1-5`
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (monitor, list) = match s.rsplit_once(':') {
+            Some((monitor, list)) => { (Some(monitor.to_string()), list) }
+            None => { (None, s) }
+        };
+
+        let id = |id: &str| match id.trim().parse::<i64>() {
+            Ok(id) if id > 0 => { Ok(id) }
+            _ => { Err(format!("invalid workspace id '{id}', has to be a positive number")) }
+        };
+
+        let mut ids = vec![];
+        for part in list.split(',') {
+            match part.split_once('-') {
+                Some((start, end)) => {
+                    let (start, end) = (id(start)?, id(end)?);
+                    if start > end { return Err(format!("invalid range '{part}', the start is after the end")) }
+
+                    ids.extend(start..=end);
+                }
+                None => { ids.push(id(part)?) }
+            }
+        }
+
+        Ok(Self { monitor, ids })
+    }
+}
+
+/// Options to sort and group a list of entities
+#[derive(Args, Clone, Debug, Default)]
+#[command(about = None, long_about = None)]
+pub struct Arrangement {
+    /// Sort by this field, which is a path separated by dots (e.g. workspace.id)
+    #[clap(long)]
+    pub sort_by: Option<String>,
+    /// Group into an object keyed by the name of the monitor or workspace
+    #[clap(long)]
+    pub group_by: Option<Grouping>,
+}
+
+/// What entities can be grouped by
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Grouping {
+    Monitor,
+    Workspace,
+}
+
+impl Arrangement {
+    /// Sorts and groups the list, given the fields containing the monitor and workspace names of its items.
+    /// The items are finished (e.g. reduced to selected fields) afterwards, so sorting and grouping can still use all fields.
+    pub fn apply(&self, list: Value, monitor_field: &str, workspace_field: &str, finish: impl Fn(Value) -> Value) -> Value {
+        let Value::Array(mut items) = list else { return finish(list) };
+
+        // items without the field are sorted last
+        if let Some(sort_by) = &self.sort_by {
+            items.sort_by(|a, b| match (field(a, sort_by), field(b, sort_by)) {
+                (Some(a), Some(b)) => { order(a, b) }
+                (a, b) => { b.is_some().cmp(&a.is_some()) }
+            });
+        }
+
+        let Some(grouping) = self.group_by else { return finish(Value::Array(items)) };
+        let key_field = if grouping == Grouping::Monitor { monitor_field } else { workspace_field };
+
+        let mut groups = Map::new();
+        for item in items {
+            let key = match field(&item, key_field) {
+                Some(Value::String(key)) => { key.clone() }
+                Some(key) => { key.to_string() }
+                None => { String::new() }
+            };
+
+            if let Value::Array(group) = groups.entry(key).or_insert_with(|| Value::Array(vec![])) { group.push(finish(item)) }
+        }
+
+        Value::Object(groups)
+    }
+}
+
+/// Returns the field of an item at the path separated by dots
+fn field<'a>(item: &'a Value, path: &str) -> Option<&'a Value> {
+    item.pointer(&format!("/{}", path.replace('.', "/")))
+}
+
+/// Orders values by their type first (null, booleans, numbers, strings, lists, objects), then numbers by their value and strings lexicographically.
+/// Lists and objects are considered equal to each other, so this is still a total order.
+fn order(a: &Value, b: &Value) -> Ordering {
+    let rank = |value: &Value| match value {
+        Value::Null => { 0 }
+        Value::Bool(_) => { 1 }
+        Value::Number(_) => { 2 }
+        Value::String(_) => { 3 }
+        Value::Array(_) => { 4 }
+        Value::Object(_) => { 5 }
+    };
+
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => { a.as_f64().unwrap_or_default().total_cmp(&b.as_f64().unwrap_or_default()) }
+        (Value::String(a), Value::String(b)) => { a.cmp(b) }
+        (Value::Bool(a), Value::Bool(b)) => { a.cmp(b) }
+        (a, b) => { rank(a).cmp(&rank(b)) }
+    }
+}
+
+/// Filters for clients based on their attributes, every given filter has to match
+#[derive(Args, Clone, Debug, Default)]
+#[command(about = None, long_about = None)]
+pub struct ClientFilters {
+    /// Only watch clients whose class matches this regex
+    #[clap(short, long)]
+    pub class: Option<Regex>,
+    /// Only watch clients whose title matches this regex
+    #[clap(short, long)]
+    pub title: Option<Regex>,
+    /// Only watch clients which are floating or not
+    #[clap(long)]
+    pub floating: Option<bool>,
+    /// Only watch clients which are fullscreen or not (in any mode)
+    #[clap(long)]
+    pub fullscreen: Option<bool>,
+    /// Only watch clients which are pinned or not
+    #[clap(long)]
+    pub pinned: Option<bool>,
+    /// Only watch clients which use xwayland or not
+    #[clap(long)]
+    pub xwayland: Option<bool>,
+    /// Only watch clients of the process with this id
+    #[clap(long)]
+    pub pid: Option<i64>,
+}
+
+impl ClientFilters {
+    /// Whether the client matches all filters
+    pub fn matches(&self, client: &Client) -> bool {
+        let text = |field: &str| client.extra.get(field).and_then(Value::as_str).unwrap_or_default();
+        let flag = |field: &str| client.extra.get(field).and_then(Value::as_bool).unwrap_or_default();
+
+        // newer versions report the fullscreen mode instead, which is 0 if not fullscreen
+        let fullscreen = match client.extra.get("fullscreen") {
+            Some(Value::Number(mode)) => { mode.as_i64().unwrap_or_default() != 0 }
+            _ => { flag("fullscreen") }
+        };
+
+        self.class.as_ref().is_none_or(|r| r.is_match(text("class")))
+            && self.title.as_ref().is_none_or(|r| r.is_match(text("title")))
+            && self.floating.is_none_or(|f| f == flag("floating"))
+            && self.fullscreen.is_none_or(|f| f == fullscreen)
+            && self.pinned.is_none_or(|f| f == flag("pinned"))
+            && self.xwayland.is_none_or(|f| f == flag("xwayland"))
+            && self.pid.is_none_or(|p| Some(p) == client.extra.get("pid").and_then(Value::as_i64))
+    }
+}
+
+/// Prepares the client data
+pub fn prepare_clients(responses: &Responses, monitor: &Option<String>, workspace: &Option<String>, filters: &ClientFilters) -> anyhow::Result<Value> {
+    // map monitor ids to names
+    let monitors: Vec<MonitorRef> = parse(responses, "monitors")?;
+    let monitor_names: HashMap<i64, String> = monitors.into_iter().map(|m| (m.id, m.name)).collect();
+
+    let mut clients: Vec<Client> = parse(responses, "clients")?;
+
+    // filter by workspace and attributes
+    if let Some(workspace) = workspace {
+        clients.retain(|c| c.is_on_workspace(workspace));
+    }
+
+    clients.retain(|c| filters.matches(c));
+
+    // associate and filter with monitors
+    for client in clients.iter_mut() {
+        client.enrich(&monitor_names);
+    }
+
+    // still keep clients without a known monitor when no monitor filter is active
+    if let Some(monitor) = monitor {
+        clients.retain(|c| c.monitor_name.as_ref() == Some(monitor));
+    }
+
+    serde_json::to_value(clients).context("failed to serialize clients")
+}
+
+/// Prepares the data of the active window, which is null if no window is focused
+/// Like with the clients, the monitor data is also used to add the monitor name
+pub fn prepare_active_window(responses: &Responses) -> anyhow::Result<Value> {
+    let monitors: Vec<MonitorRef> = parse(responses, "monitors")?;
+    let monitor_names: HashMap<i64, String> = monitors.into_iter().map(|m| (m.id, m.name)).collect();
+
+    // hyprland returns an empty object if there is no active window
+    let window: Value = parse(responses, "activewindow")?;
+    if window.as_object().is_some_and(|o| o.is_empty()) { return Ok(Value::Null) }
+
+    let mut client: Client = parse(responses, "activewindow")?;
+    client.enrich(&monitor_names);
+
+    serde_json::to_value(client).context("failed to serialize active window")
+}
+
+/// Prepares the layer data
+/// Hyprland returns the layers nested by monitor and level, they are flattened into a list annotated with both
+pub fn prepare_layers(responses: &Responses, on_monitor: &Option<String>) -> anyhow::Result<Value> {
+    let monitors: Vec<MonitorRef> = parse(responses, "monitors")?;
+    let monitor_ids: HashMap<String, i64> = monitors.into_iter().map(|m| (m.name, m.id)).collect();
+
+    let nested: BTreeMap<String, MonitorLayers> = parse(responses, "layers")?;
+
+    let mut layers = vec![];
+    for (monitor, MonitorLayers { levels }) in nested {
+        // Remove layers not on monitor
+        if on_monitor.as_ref().is_some_and(|m| m != &monitor) { continue }
+
+        for (level, surfaces) in levels {
+            for mut layer in surfaces {
+                layer.enrich(&monitor, &level, &monitor_ids);
+                layers.push(layer);
+            }
+        }
+    }
+
+    serde_json::to_value(layers).context("failed to serialize layers")
+}
+
+/// Prepares the device data, optionally only including devices with the given name
+pub fn prepare_devices(responses: &Responses, name: &Option<String>) -> anyhow::Result<Value> {
+    let mut devices: Devices = parse(responses, "devices")?;
+
+    if let Some(name) = name {
+        devices.retain_named(name);
+    }
+
+    serde_json::to_value(devices).context("failed to serialize devices")
+}
+
+/// Prepares the data of a single keyboard, which is null if it does not exist
+/// Without a name, the main keyboard is used, or the first one if hyprland does not report which one that is
+pub fn prepare_keyboard(responses: &Responses, name: &Option<String>) -> anyhow::Result<Value> {
+    let devices: Devices = parse(responses, "devices")?;
+
+    let keyboard = if let Some(name) = name {
+        devices.keyboards.into_iter().find(|k| &k.name == name)
+    } else {
+        let main = devices.keyboards.iter().position(|k| k.main).unwrap_or_default();
+        devices.keyboards.into_iter().nth(main)
+    };
+
+    serde_json::to_value(keyboard).context("failed to serialize keyboard")
+}
+
+/// Prepares the name of the current submap
+/// Older hyprland versions cannot be queried for it, in which case the default submap is assumed
+pub fn prepare_submap(responses: &Responses) -> anyhow::Result<Value> {
+    let name = match responses.get("submap") {
+        Some(Some(Value::String(name))) => { name.as_str() }
+        _ => { "" }
+    };
+
+    Ok(Value::String(submap_name(name)))
+}
+
+/// Returns the name of a submap as reported by hyprland, which reports the default one as empty
+pub fn submap_name(name: &str) -> String {
+    let name = name.trim();
+    if name.is_empty() { "default".to_string() } else { name.to_string() }
+}
+
+/// Parses the response to a request into its model, errors name the entity and the offending field
+fn parse<'a, T: Deserialize<'a>>(responses: &'a Responses, request: &str) -> anyhow::Result<T> {
+    let data = responses.get(request)
+        .with_context(|| format!("socket 1 did not return a response for {request}"))?.as_ref()
+        .with_context(|| format!("socket 1 did not return valid json for {request}"))?;
+
+    T::deserialize(data).with_context(|| format!("socket 1 returned unexpected data for {request}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use super::{parse_response, prepare_submap, prepare_workspaces, Arrangement, Grouping, Persistent, Responses};
+
+    #[test]
+    fn parses_persistent_workspaces() {
+        assert_eq!(Persistent::parse("1-3,5"), Ok(Persistent { monitor: None, ids: vec![1, 2, 3, 5] }));
+        assert_eq!(Persistent::parse("DP-1:4"), Ok(Persistent { monitor: Some("DP-1".into()), ids: vec![4] }));
+        assert_eq!(Persistent::parse("HDMI-A-1:6-7, 9").unwrap().ids, vec![6, 7, 9]);
+    }
+
+    #[test]
+    fn rejects_invalid_persistent_workspaces() {
+        assert_eq!(Persistent::parse("3-1"), Err("invalid range '3-1', the start is after the end".into()));
+        assert_eq!(Persistent::parse("0-2"), Err("invalid workspace id '0', has to be a positive number".into()));
+        assert_eq!(Persistent::parse("DP-1:a"), Err("invalid workspace id 'a', has to be a positive number".into()));
+        assert!(Persistent::parse("DP-1:").is_err());
+        assert!(Persistent::parse("1,").is_err());
+    }
+
+    #[test]
+    fn sorts_mixed_types() {
+        // sorting with an inconsistent order may panic, so use enough items of every type
+        let items: Vec<Value> = (0..60).map(|i| match i % 4 {
+            0 => { json!({ "key": i }) }
+            1 => { json!({ "key": i.to_string() }) }
+            2 => { json!({ "key": null }) }
+            _ => { json!({ "other": i }) }
+        }).collect();
+
+        let arrangement = Arrangement { sort_by: Some("key".into()), group_by: None };
+        let sorted = arrangement.apply(Value::Array(items), "monitor", "workspace", |v| v);
+        let keys: Vec<&Value> = sorted.as_array().unwrap().iter().map(|i| &i["key"]).collect();
+
+        assert_eq!(keys[0], &Value::Null);
+        assert_eq!(keys[15], &json!(0));
+        assert_eq!(keys[16], &json!(4));
+        assert_eq!(keys[30], &json!("1"));
+        assert_eq!(keys[31], &json!("13"));
+        assert_eq!(keys[45], &Value::Null); // missing fields are last
+    }
+
+    #[test]
+    fn groups_after_sorting() {
+        let clients = json!([
+            { "class": "kitty", "workspace": { "name": "2" } },
+            { "class": "firefox", "workspace": { "name": "1" } },
+            { "class": "foot", "workspace": { "name": "2" } },
+        ]);
+
+        let arrangement = Arrangement { sort_by: Some("class".into()), group_by: Some(Grouping::Workspace) };
+        let grouped = arrangement.apply(clients, "monitorName", "workspace.name", |c| json!(c["class"]));
+
+        assert_eq!(grouped, json!({ "1": ["firefox"], "2": ["foot", "kitty"] }));
+    }
+
+    #[test]
+    fn parses_submap_responses() {
+        let submap = |response: &str| {
+            let responses = Responses::from([("submap".to_string(), parse_response("submap", response))]);
+            prepare_submap(&responses).unwrap()
+        };
+
+        // as answered by hyprland to j/submap
+        assert_eq!(submap("{\"resize\"}\n"), json!("resize"));
+        assert_eq!(submap("{\"default\"}\n"), json!("default"));
+        // older versions don't know the request
+        assert_eq!(submap("unknown request"), json!("default"));
+    }
+
+    #[test]
+    fn adds_persistent_workspaces() {
+        let responses = Responses::from([
+            ("monitors".to_string(), Some(json!([
+                { "id": 1, "name": "HDMI-A-1", "focused": false, "activeWorkspace": { "id": 4, "name": "4" }, "specialWorkspace": { "id": 0, "name": "" } },
+                { "id": 0, "name": "DP-1", "focused": true, "activeWorkspace": { "id": 1, "name": "1" }, "specialWorkspace": { "id": 0, "name": "" } },
+            ]))),
+            ("workspaces".to_string(), Some(json!([
+                { "id": 1, "name": "1", "monitor": "DP-1", "windows": 2 },
+                { "id": 4, "name": "4", "monitor": "HDMI-A-1", "windows": 1 },
+            ]))),
+        ]);
+        let persistent = [Persistent::parse("1-3").unwrap()];
+
+        // without a monitor, placeholders are on the one with the lowest id, existing workspaces are kept as they are
+        let workspaces = prepare_workspaces(&responses, &None, &None, &persistent, false).unwrap();
+        let summary: Vec<Value> = workspaces.as_array().unwrap().iter()
+            .map(|w| json!([w["id"], w["monitor"], w["exists"], w["shown"], w["active"], w["windows"]]))
+            .collect();
+
+        assert_eq!(summary, vec![
+            json!([1, "DP-1", true, true, true, 2]),
+            json!([2, "DP-1", false, false, false, 0]),
+            json!([3, "DP-1", false, false, false, 0]),
+            json!([4, "HDMI-A-1", true, true, false, 1]),
+        ]);
+
+        // with a monitor, placeholders are on that one
+        let workspaces = prepare_workspaces(&responses, &Some("HDMI-A-1".into()), &None, &persistent, false).unwrap();
+        let ids: Vec<Value> = workspaces.as_array().unwrap().iter().map(|w| json!([w["id"], w["monitor"]])).collect();
+
+        assert_eq!(ids, vec![json!([2, "HDMI-A-1"]), json!([3, "HDMI-A-1"]), json!([4, "HDMI-A-1"])]);
+    }
+}
@@ -0,0 +1,68 @@
+use serde_json::{Map, Value};
+
+/// Projects the data down to the given fields, which are paths separated by dots (e.g. `workspace.name`).
+/// Lists are projected item by item, other objects as a whole. Fields which don't exist are left out.
+pub fn project(data: Value, fields: &[String]) -> Value {
+    let paths: Vec<Vec<&str>> = fields.iter().map(|f| f.split('.').collect()).collect();
+
+    match data {
+        Value::Array(items) => { Value::Array(items.into_iter().map(|item| project_item(item, &paths)).collect()) }
+        data => { project_item(data, &paths) }
+    }
+}
+
+fn project_item(item: Value, paths: &[Vec<&str>]) -> Value {
+    let Value::Object(item) = item else { return item };
+
+    let mut projected = Map::new();
+    for path in paths {
+        copy(&item, &mut projected, path);
+    }
+
+    Value::Object(projected)
+}
+
+/// Copies the value at the path from one object to the other, creating the nested objects on the way
+fn copy(from: &Map<String, Value>, to: &mut Map<String, Value>, path: &[&str]) {
+    let Some((key, rest)) = path.split_first() else { return };
+    let Some(value) = from.get(*key) else { return };
+
+    match (value, rest.is_empty()) {
+        (value, true) => { to.insert(key.to_string(), value.clone()); }
+        (Value::Object(from), false) => {
+            let entry = to.entry(key.to_string()).or_insert_with(|| Value::Object(Map::new()));
+
+            // a parent may have already been copied as a whole
+            if let Value::Object(to) = entry { copy(from, to, rest) }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::project;
+
+    #[test]
+    fn projects_items() {
+        let clients = json!([
+            { "class": "kitty", "title": "vim", "pid": 1, "workspace": { "id": 1, "name": "1" } },
+            { "class": "firefox", "pid": 2, "workspace": { "id": 2, "name": "web" } },
+        ]);
+        let fields = ["class", "title", "workspace.name", "size.x"].map(String::from);
+
+        assert_eq!(project(clients, &fields), json!([
+            { "class": "kitty", "title": "vim", "workspace": { "name": "1" } },
+            { "class": "firefox", "workspace": { "name": "web" } },
+        ]));
+    }
+
+    #[test]
+    fn projects_objects() {
+        let window = json!({ "class": "kitty", "workspace": { "id": 1, "name": "1" } });
+
+        assert_eq!(project(window.clone(), &["workspace".into(), "workspace.id".into()]), json!({ "workspace": { "id": 1, "name": "1" } }));
+        assert_eq!(project(json!(null), &["class".into()]), json!(null));
+    }
+}
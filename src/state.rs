@@ -0,0 +1,242 @@
+use serde_json::{Map, Value};
+use crate::hypr::HyprEvent;
+use crate::prepare::{query, Responses};
+
+/// Cached responses of socket 1, to which cheap events (e.g. title changes) are applied directly instead of querying hyprland again.
+/// Structural events (e.g. new windows) cannot be applied, in which case everything has to be queried again.
+#[derive(Clone, Debug, Default)]
+pub struct State {
+    responses: Option<Responses>,
+    /// Whether hyprland sends windowtitlev2, making windowtitle redundant
+    titles_v2: bool,
+}
+
+impl State {
+    /// Returns the cached responses, none if nothing is cached
+    pub fn responses(&self) -> Option<&Responses> {
+        self.responses.as_ref()
+    }
+
+    /// Queries the requests again, replacing the cached responses
+    pub fn refresh(&mut self, requests: &[&str]) -> anyhow::Result<()> {
+        self.responses = Some(query(requests)?);
+        Ok(())
+    }
+
+    /// Discards the cached responses, e.g. because they are stale
+    pub fn invalidate(&mut self) {
+        self.responses = None;
+    }
+
+    /// Applies the event to the cached responses, returns false if that is not possible and they have to be queried again.
+    /// Every event has to be applied, even if it is not relevant for the watched data, as it might still change the cached responses.
+    /// Events which don't change any cached response are applied without doing anything.
+    /// If this returns false, the cached responses are discarded.
+    pub fn apply(&mut self, event: &HyprEvent) -> bool {
+        if let HyprEvent::WindowTitleV2 { .. } = event { self.titles_v2 = true }
+
+        let titles_v2 = self.titles_v2;
+        let Some(responses) = &mut self.responses else { return false };
+
+        if !responses.keys().any(|request| affects(event, request)) { return true }
+
+        let applied = match event {
+            HyprEvent::Submap { name } => {
+                if let Some(submap) = responses.get_mut("submap") {
+                    *submap = Some(Value::String(name.clone()));
+                }
+
+                true
+            }
+            // the title is sent with windowtitlev2 anyway
+            HyprEvent::WindowTitle { .. } => { titles_v2 }
+            HyprEvent::WindowTitleV2 { address, title } => {
+                let address = format!("0x{address}");
+
+                for window in windows(responses).into_iter().filter(|w| has_address(w, &address)) {
+                    window.insert("title".into(), Value::String(title.clone()));
+                }
+
+                for workspace in objects(responses, "workspaces").into_iter().filter(|w| w.get("lastwindow").and_then(Value::as_str) == Some(&address)) {
+                    workspace.insert("lastwindowtitle".into(), Value::String(title.clone()));
+                }
+
+                true
+            }
+            HyprEvent::ChangeFloatingMode { address, floating } => {
+                let address = format!("0x{address}");
+
+                for window in windows(responses).into_iter().filter(|w| has_address(w, &address)) {
+                    window.insert("floating".into(), Value::Bool(*floating));
+                }
+
+                true
+            }
+            HyprEvent::ActiveWindowV2 { address } => {
+                // workspaces would need their last window updated too
+                if address.is_empty() || responses.contains_key("workspaces") { return false }
+
+                focus(responses, &format!("0x{address}"))
+            }
+            _ => false
+        };
+
+        if !applied { self.invalidate() }
+        applied
+    }
+}
+
+/// Whether the response to the request might change because of the event
+fn affects(event: &HyprEvent, request: &str) -> bool {
+    match event.name() {
+        "submap" => { request == "submap" }
+        "activelayout" => { request == "devices" }
+        "openlayer" | "closelayer" => { request == "layers" }
+        "bell" | "screencast" => { false }
+        "workspace" | "workspacev2" | "focusedmon" | "focusedmonv2" | "activewindow" | "activewindowv2" | "fullscreen" |
+        "createworkspace" | "createworkspacev2" | "destroyworkspace" | "destroyworkspacev2" | "moveworkspace" |
+        "moveworkspacev2" | "renameworkspace" | "activespecial" | "activespecialv2" | "openwindow" | "closewindow" |
+        "movewindow" | "movewindowv2" | "changefloatingmode" | "urgent" | "minimize" | "windowtitle" | "windowtitlev2" |
+        "togglegroup" | "moveintogroup" | "moveoutofgroup" | "ignoregrouplock" | "lockgroups" | "pin" => {
+            matches!(request, "monitors" | "workspaces" | "clients" | "activewindow")
+        }
+        // monitor changes, config reloads and unknown events might change anything
+        _ => { true }
+    }
+}
+
+/// Moves the focus to the window with the given address, by updating the focus history and the active window
+fn focus(responses: &mut Responses, address: &str) -> bool {
+    // the active window can only be updated with the data of the clients
+    let Some(Some(Value::Array(clients))) = responses.get_mut("clients") else {
+        return !responses.contains_key("activewindow")
+    };
+
+    let Some(previous) = clients.iter()
+        .find(|c| c.as_object().is_some_and(|c| has_address(c, address)))
+        .and_then(|c| c.get("focusHistoryID")).and_then(Value::as_i64) else { return false };
+
+    // every window which was focused more recently moves back by one
+    for client in clients.iter_mut().filter_map(Value::as_object_mut) {
+        let Some(id) = client.get("focusHistoryID").and_then(Value::as_i64) else { return false };
+
+        let id = if has_address(client, address) { 0 } else if id < previous { id + 1 } else { id };
+        client.insert("focusHistoryID".into(), Value::from(id));
+    }
+
+    let window = clients.iter().find(|c| c.as_object().is_some_and(|c| has_address(c, address))).cloned();
+    if let (Some(active), Some(window)) = (responses.get_mut("activewindow"), window) {
+        *active = Some(window);
+    }
+
+    true
+}
+
+/// Returns all cached windows, which are the clients and the active window
+fn windows(responses: &mut Responses) -> Vec<&mut Map<String, Value>> {
+    let mut windows = vec![];
+
+    for (request, response) in responses.iter_mut() {
+        match (request.as_str(), response) {
+            ("clients", Some(Value::Array(clients))) => { windows.extend(clients.iter_mut().filter_map(Value::as_object_mut)) }
+            ("activewindow", Some(Value::Object(window))) => { windows.push(window) }
+            _ => {}
+        }
+    }
+
+    windows
+}
+
+/// Returns the objects in the cached response to a request which returns a list
+fn objects<'a>(responses: &'a mut Responses, request: &str) -> Vec<&'a mut Map<String, Value>> {
+    match responses.get_mut(request) {
+        Some(Some(Value::Array(list))) => { list.iter_mut().filter_map(Value::as_object_mut).collect() }
+        _ => { vec![] }
+    }
+}
+
+fn has_address(window: &Map<String, Value>, address: &str) -> bool {
+    window.get("address").and_then(Value::as_str) == Some(address)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use crate::hypr::HyprEvent;
+    use crate::prepare::Responses;
+    use super::State;
+
+    fn cached(requests: &[(&str, Value)]) -> State {
+        let responses: Responses = requests.iter().map(|(r, v)| (r.to_string(), Some(v.clone()))).collect();
+        State { responses: Some(responses), titles_v2: false }
+    }
+
+    fn clients() -> Value {
+        json!([
+            { "address": "0x1", "title": "one", "floating": false, "fullscreen": false, "focusHistoryID": 1 },
+            { "address": "0x2", "title": "two", "floating": false, "fullscreen": false, "focusHistoryID": 0 },
+            { "address": "0x3", "title": "three", "floating": false, "fullscreen": false, "focusHistoryID": 2 },
+        ])
+    }
+
+    fn response<'a>(state: &'a State, request: &str) -> &'a Value {
+        state.responses().unwrap().get(request).unwrap().as_ref().unwrap()
+    }
+
+    #[test]
+    fn updates_titles() {
+        let mut state = cached(&[("clients", clients()), ("activewindow", clients()[1].clone())]);
+
+        assert!(state.apply(&HyprEvent::WindowTitleV2 { address: "2".into(), title: "new, title".into() }));
+        assert!(state.apply(&HyprEvent::WindowTitle { address: "2".into() }));
+
+        assert_eq!(response(&state, "clients")[1]["title"], "new, title");
+        assert_eq!(response(&state, "activewindow")["title"], "new, title");
+        assert_eq!(response(&state, "clients")[0]["title"], "one");
+
+        // without windowtitlev2, the title is not known
+        assert!(!cached(&[("clients", clients())]).apply(&HyprEvent::WindowTitle { address: "2".into() }));
+    }
+
+    #[test]
+    fn moves_focus() {
+        let mut state = cached(&[("clients", clients()), ("activewindow", clients()[1].clone()), ("monitors", json!([]))]);
+
+        assert!(state.apply(&HyprEvent::ActiveWindowV2 { address: "3".into() }));
+
+        let ids: Vec<i64> = response(&state, "clients").as_array().unwrap().iter().map(|c| c["focusHistoryID"].as_i64().unwrap()).collect();
+        assert_eq!(ids, vec![2, 1, 0]);
+        assert_eq!(response(&state, "activewindow")["address"], "0x3");
+    }
+
+    #[test]
+    fn requires_query_for_structural_events() {
+        let mut state = cached(&[("clients", clients())]);
+
+        assert!(!state.apply(&HyprEvent::OpenWindow { address: "4".into(), workspace: "1".into(), class: "kitty".into(), title: "kitty".into() }));
+        assert!(!state.apply(&HyprEvent::ActiveWindowV2 { address: "4".into() }));
+        assert!(!State::default().apply(&HyprEvent::Submap { name: "resize".into() }));
+    }
+
+    #[test]
+    fn invalidates_on_irrelevant_changes() {
+        let mut state = cached(&[("clients", clients()), ("monitors", json!([]))]);
+
+        // events not changing any cached response are skipped
+        assert!(state.apply(&HyprEvent::ActiveLayout { keyboard: "kbd".into(), layout: "German".into() }));
+        assert!(state.apply(&HyprEvent::Bell { address: "".into() }));
+        assert!(state.responses().is_some());
+
+        // moving a workspace changes the monitor of its clients, so the next title cannot be applied to the stale clients
+        assert!(!state.apply(&HyprEvent::MoveWorkspace { name: "1".into(), monitor: "HDMI-A-1".into() }));
+        assert!(state.responses().is_none());
+        assert!(!state.apply(&HyprEvent::WindowTitleV2 { address: "2".into(), title: "new".into() }));
+    }
+
+    #[test]
+    fn requires_query_for_fullscreen() {
+        // the event does not name the window which changed
+        let mut state = cached(&[("clients", clients())]);
+        assert!(!state.apply(&HyprEvent::Fullscreen { enabled: true }));
+    }
+}
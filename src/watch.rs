@@ -0,0 +1,457 @@
+use std::os::unix::net::UnixStream;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use anyhow::anyhow;
+use clap::{Args, Subcommand, ValueEnum};
+use serde_json::{Map, Value};
+use crate::filter::Filter;
+use crate::hypr::{open_events, use_newest_instance, EventReader, HyprEvent};
+use crate::prepare::{Arrangement, ClientFilters, Persistent, prepare_active_window, prepare_clients, prepare_devices, prepare_keyboard, prepare_layers, prepare_monitors, prepare_submap, prepare_workspaces, query, Responses};
+use crate::project::project;
+use crate::state::State;
+
+/// Entities which can be watched
+#[derive(Subcommand, Clone, Debug)]
+pub enum Target {
+    /// Watch changes in monitors
+    Monitors,
+    /// Watch changes in workspaces
+    Workspaces {
+        /// Only watch workspaces on monitor
+        #[clap(short, long)]
+        monitor: Option<String>,
+
+        /// Only watch for workspaces with special status
+        #[clap(short, long)]
+        special: Option<bool>,
+
+        /// Always include these workspaces, even if they don't exist (e.g. 1-10 or DP-1:1-5,8 for a monitor, can be repeated)
+        #[clap(short, long, value_parser = Persistent::parse)]
+        persistent: Vec<Persistent>,
+
+        /// Attach the clients (address, class, title and focused) to every workspace
+        #[clap(short, long)]
+        clients: bool,
+
+        #[command(flatten)]
+        arrangement: Arrangement,
+    },
+    /// Watch changes in clients (windows)
+    Clients {
+        /// Only watch clients on monitor
+        #[clap(short, long)]
+        monitor: Option<String>,
+        /// Only watch clients on workspace
+        #[clap(short, long)]
+        workspace: Option<String>,
+
+        #[command(flatten)]
+        filters: ClientFilters,
+
+        #[command(flatten)]
+        arrangement: Arrangement,
+    },
+    /// Watch changes of the active (focused) window
+    #[command(name = "activewindow")]
+    ActiveWindow,
+    /// Watch changes in layer-shell surfaces (bars, notifications, etc.)
+    Layers {
+        /// Only watch layers on monitor
+        #[clap(short, long)]
+        monitor: Option<String>,
+    },
+    /// Watch changes in input devices (e.g. the keyboard layout)
+    Devices {
+        /// Only watch devices with name
+        #[clap(short, long)]
+        name: Option<String>,
+    },
+    /// Watch changes of a single keyboard, e.g. its layout
+    Keyboard {
+        /// Name of the keyboard to watch, defaults to the main keyboard
+        #[clap(short, long)]
+        name: Option<String>,
+    },
+    /// Watch the currently active submap
+    Submap,
+    /// Watch multiple entities at once, printed in an object keyed by their names
+    Multi {
+        /// Entities to watch, with their default options
+        #[clap(required = true)]
+        targets: Vec<Kind>,
+    },
+}
+
+/// Entities which can be watched together with others
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Monitors,
+    Workspaces,
+    Clients,
+    #[value(name = "activewindow")]
+    ActiveWindow,
+    Layers,
+    Devices,
+    Keyboard,
+    Submap,
+}
+
+impl Kind {
+    /// Returns the target for this entity, with default options
+    pub fn target(&self) -> Target {
+        match self {
+            Kind::Monitors => { Target::Monitors }
+            Kind::Workspaces => { Target::Workspaces { monitor: None, special: None, persistent: vec![], clients: false, arrangement: Arrangement::default() } }
+            Kind::Clients => { Target::Clients { monitor: None, workspace: None, filters: ClientFilters::default(), arrangement: Arrangement::default() } }
+            Kind::ActiveWindow => { Target::ActiveWindow }
+            Kind::Layers => { Target::Layers { monitor: None } }
+            Kind::Devices => { Target::Devices { name: None } }
+            Kind::Keyboard => { Target::Keyboard { name: None } }
+            Kind::Submap => { Target::Submap }
+        }
+    }
+
+    /// Name of the entity, as used on the command line
+    pub fn name(&self) -> String {
+        self.to_possible_value().map(|v| v.get_name().to_string()).unwrap_or_default()
+    }
+}
+
+impl Target {
+    /// Requests to socket 1 which are needed to prepare the data of the target
+    pub fn requests(&self) -> Vec<&'static str> {
+        match self {
+            Target::Monitors => { vec!["monitors"] }
+            Target::Workspaces { clients: false, .. } => { vec!["workspaces", "monitors"] }
+            Target::Workspaces { clients: true, .. } => { vec!["workspaces", "monitors", "clients"] }
+            Target::Clients { .. } => { vec!["clients", "monitors"] }
+            Target::ActiveWindow => { vec!["activewindow", "monitors"] }
+            Target::Layers { .. } => { vec!["layers", "monitors"] }
+            Target::Devices { .. } | Target::Keyboard { .. } => { vec!["devices"] }
+            Target::Submap => { vec!["submap"] }
+            Target::Multi { targets } => {
+                let mut requests: Vec<&str> = targets.iter().flat_map(|k| k.target().requests()).collect();
+                requests.sort();
+                requests.dedup();
+                requests
+            }
+        }
+    }
+
+    /// Prepares the data of the target from the responses to its requests
+    pub fn prepare(&self, responses: &Responses) -> anyhow::Result<Value> {
+        match self {
+            Target::Monitors => { prepare_monitors(responses) }
+            Target::Workspaces { monitor, special, persistent, clients, .. } => { prepare_workspaces(responses, monitor, special, persistent, *clients) }
+            Target::Clients { monitor, workspace, filters, .. } => { prepare_clients(responses, monitor, workspace, filters) }
+            Target::ActiveWindow => { prepare_active_window(responses) }
+            Target::Layers { monitor } => { prepare_layers(responses, monitor) }
+            Target::Devices { name } => { prepare_devices(responses, name) }
+            Target::Keyboard { name } => { prepare_keyboard(responses, name) }
+            Target::Submap => { prepare_submap(responses) }
+            Target::Multi { targets } => {
+                let mut map = Map::new();
+                for kind in targets {
+                    map.insert(kind.name(), kind.target().prepare(responses)?);
+                }
+
+                Ok(Value::Object(map))
+            }
+        }
+    }
+
+    /// Reduces the prepared data to what should be printed, the items matching the filter and their selected fields, sorted and grouped.
+    /// For multiple targets, the lists and objects of every target are reduced separately, as are the lists of devices.
+    pub fn shape(&self, data: Value, options: &WatchOptions) -> Value {
+        match (self, data) {
+            (Target::Multi { targets }, Value::Object(mut map)) => {
+                for kind in targets {
+                    let name = kind.name();
+
+                    if let Some(value) = map.remove(&name) {
+                        let value = if value.is_array() || value.is_object() { kind.target().shape(value, options) } else { value };
+                        map.insert(name, value);
+                    }
+                }
+
+                Value::Object(map)
+            }
+            // the submap is a single name without any items
+            (Target::Submap, data) => { data }
+            (Target::Devices { .. }, Value::Object(devices)) => {
+                // devices are listed by their type, which are reduced separately
+                Value::Object(devices.into_iter()
+                    .map(|(kind, list)| (kind, if list.is_array() { Target::Devices { name: None }.shape(list, options) } else { list }))
+                    .collect())
+            }
+            (target, data) => {
+                let data = if let Some(filter) = &options.filter { filter.apply(data) } else { data };
+                let finish = |data| if options.fields.is_empty() { data } else { project(data, &options.fields) };
+
+                match target {
+                    Target::Workspaces { arrangement, .. } => { arrangement.apply(data, "monitor", "name", finish) }
+                    Target::Clients { arrangement, .. } => { arrangement.apply(data, "monitorName", "workspace.name", finish) }
+                    _ => { finish(data) }
+                }
+            }
+        }
+    }
+
+    /// Retrieves the current data of the target, with a single batch request
+    pub fn query(&self) -> anyhow::Result<Value> {
+        self.prepare(&query(&self.requests())?)
+    }
+
+    /// Whether the data of the target might have changed because of this event
+    pub fn is_relevant(&self, event: &HyprEvent) -> bool {
+        // matched by name, so events whose arguments could not be parsed still count
+        let names: &[&str] = match self {
+            Target::Monitors => &["focusedmon", "monitorremoved", "monitoradded"],
+            Target::Workspaces { clients, .. } => {
+                if *clients && Kind::Clients.target().is_relevant(event) { return true }
+
+                &["focusedmon", "monitorremoved", "monitoradded", "workspace", "createworkspace", "destroyworkspace",
+                    "moveworkspace", "renameworkspace", "openwindow", "closewindow", "movewindow", "activespecial"]
+            }
+            Target::Clients { .. } => &["openwindow", "closewindow", "movewindow", "changefloatingmode", "fullscreen",
+                "windowtitle", "windowtitlev2", "activewindowv2", "pin", "renameworkspace"],
+            Target::ActiveWindow => &["activewindowv2", "windowtitle", "windowtitlev2", "fullscreen", "changefloatingmode",
+                "closewindow", "renameworkspace"],
+            Target::Layers { .. } => &["openlayer", "closelayer"],
+            Target::Devices { name } | Target::Keyboard { name } => {
+                return match event {
+                    HyprEvent::ActiveLayout { keyboard, .. } => { name.as_ref().is_none_or(|n| n == keyboard) }
+                    event => { event.name() == "activelayout" }
+                }
+            }
+            Target::Submap => &["submap"],
+            Target::Multi { targets } => { return targets.iter().any(|k| k.target().is_relevant(event)) }
+        };
+
+        names.contains(&event.name())
+    }
+}
+
+/// Options changing how a target is watched
+#[derive(Args, Clone, Debug, Default)]
+#[command(about = None, long_about = None)]
+pub struct WatchOptions {
+    /// Reconnect instead of exiting when the event socket closes (e.g. hyprland restarts)
+    #[clap(short, long)]
+    pub reconnect: bool,
+    /// Give up reconnecting after this many failed attempts (retries forever by default)
+    #[clap(long, requires = "reconnect")]
+    pub retries: Option<u32>,
+    /// Print the data after every relevant event, even if it did not change
+    #[clap(short, long)]
+    pub always: bool,
+    /// Collect events arriving within this window (e.g. 20ms) after a relevant one, and only query once afterwards
+    #[clap(short, long, value_parser = parse_duration)]
+    pub debounce: Option<Duration>,
+    /// Only print items matching this expression (e.g. 'class =~ "firefox" && floating == false && workspace.id > 0')
+    #[clap(long, value_parser = Filter::parse)]
+    pub filter: Option<Filter>,
+    /// Only print these fields of every item, which are paths separated by dots (comma separated, e.g. class,workspace.name)
+    #[clap(long, value_delimiter = ',')]
+    pub fields: Vec<String>,
+}
+
+/// Parses a duration with a unit, like 20ms or 1s, plain numbers are interpreted as milliseconds
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (number, unit) = s.find(|c: char| !c.is_ascii_digit()).map(|i| s.split_at(i)).unwrap_or((s, "ms"));
+    let number: u64 = number.parse().map_err(|_| format!("invalid duration '{s}'"))?;
+
+    match unit {
+        "ms" => Ok(Duration::from_millis(number)),
+        "s" => Ok(Duration::from_secs(number)),
+        _ => Err(format!("invalid unit '{unit}', use ms or s"))
+    }
+}
+
+/// Update yielded when watching a target
+#[derive(Clone, Debug, PartialEq)]
+pub enum Update {
+    /// Current data of the target, with the events which caused it to change (none for the initial data)
+    Data { data: Value, events: Vec<HyprEvent> },
+    /// Connection to hyprland was lost, the last data is stale until reconnected
+    Disconnected,
+    /// Connection to hyprland was reestablished, fresh data follows
+    Reconnected,
+}
+
+/// Delay before the first reconnection attempt, doubled after every failed attempt
+const RECONNECT_DELAY: Duration = Duration::from_millis(100);
+/// Maximum delay between two reconnection attempts
+const RECONNECT_DELAY_MAX: Duration = Duration::from_secs(5);
+
+/// Iterator over the data of a target, first yields the current data and then new data after every relevant event.
+/// Data which did not change since it was last yielded is skipped, unless [`WatchOptions::always`] is set.
+/// Ends when the event socket is closed and reconnecting is disabled or has failed.
+pub struct Watcher {
+    target: Target,
+    options: WatchOptions,
+    events: Option<EventReader<UnixStream>>,
+    state: State,
+    last: Option<Value>,
+    initial: bool,
+    done: bool
+}
+
+impl Watcher {
+    /// Connects to the event socket to start watching the target
+    pub fn new(target: Target, options: WatchOptions) -> anyhow::Result<Self> {
+        Ok(Self { target, options, events: Some(open_events()?), state: State::default(), last: None, initial: true, done: false })
+    }
+
+    /// Updates the data based on the events, returns none if it is the same as last time
+    /// Hyprland is only queried again if the events cannot be applied to the cached state directly
+    fn snapshot(&mut self, events: &[HyprEvent]) -> Option<anyhow::Result<Update>> {
+        // all events are applied, as irrelevant ones might still change the cached responses
+        let cached = self.state.responses().is_some() && events.iter().all(|e| self.state.apply(e));
+        let events: Vec<HyprEvent> = events.iter().filter(|e| self.target.is_relevant(e)).cloned().collect();
+
+        let data = if cached { Ok(()) } else { self.state.refresh(&self.target.requests()) }
+            .and_then(|_| self.target.prepare(self.state.responses().expect("state has just been refreshed")))
+            .map(|data| self.target.shape(data, &self.options));
+
+        let data = match data {
+            Ok(data) => { data }
+            Err(e) => {
+                self.state.invalidate();
+                return Some(Err(e))
+            }
+        };
+
+        if !self.options.always && self.last.as_ref() == Some(&data) { return None }
+        self.last = Some(data.clone());
+
+        Some(Ok(Update::Data { data, events }))
+    }
+
+    /// Tries to connect to the event socket again, backing off between the attempts
+    fn reconnect(&mut self) -> anyhow::Result<EventReader<UnixStream>> {
+        let mut delay = RECONNECT_DELAY;
+        let mut attempts = 0;
+
+        loop {
+            sleep(delay);
+
+            // hyprland might have been restarted under a new signature, leaving the old socket behind, so try the newest instance too
+            match open_events().or_else(|e| if use_newest_instance() { open_events() } else { Err(e) }) {
+                Ok(events) => { return Ok(events) }
+                Err(e) => {
+                    attempts += 1;
+                    if self.options.retries.is_some_and(|max| attempts >= max) {
+                        return Err(e.context(format!("failed to reconnect after {attempts} attempts")));
+                    }
+
+                    delay = (delay * 2).min(RECONNECT_DELAY_MAX);
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for Watcher {
+    type Item = anyhow::Result<Update>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done { return None }
+
+        if self.initial {
+            self.initial = false;
+            self.state.invalidate();
+            self.last = None;
+            return self.snapshot(&[]);
+        }
+
+        loop {
+            let Some(reader) = &mut self.events else {
+                return match self.reconnect() {
+                    Ok(events) => {
+                        self.events = Some(events);
+                        self.initial = true;
+                        Some(Ok(Update::Reconnected))
+                    }
+                    Err(e) => {
+                        self.done = true;
+                        Some(Err(e))
+                    }
+                }
+            };
+
+            let mut events = match reader.read_events() {
+                Ok(e) => { e }
+                Err(e) => {
+                    // the connection is broken (e.g. reset), which is handled like a closed socket
+                    self.events = None;
+
+                    if self.options.reconnect {
+                        return Some(Ok(Update::Disconnected))
+                    } else {
+                        self.done = true;
+                        return Some(Err(e))
+                    }
+                }
+            };
+
+            // Socket has been closed
+            if events.is_empty() {
+                self.events = None;
+
+                if self.options.reconnect {
+                    return Some(Ok(Update::Disconnected))
+                } else {
+                    self.done = true;
+                    return None
+                }
+            }
+
+            if events.iter().any(|e| self.target.is_relevant(e)) {
+                if let Some(debounce) = self.options.debounce {
+                    match settle(reader, debounce) {
+                        Ok(more) => { events.extend(more) }
+                        Err(e) => { return Some(Err(e)) }
+                    }
+                }
+
+                if let Some(update) = self.snapshot(&events) { return Some(update) }
+            } else {
+                // keep the cached responses up to date, or discard them if they have become stale
+                for event in &events {
+                    if !self.state.apply(event) { break }
+                }
+            }
+        }
+    }
+}
+
+/// Collects all events arriving on the socket until the window has passed
+fn settle(reader: &mut EventReader<UnixStream>, window: Duration) -> anyhow::Result<Vec<HyprEvent>> {
+    let deadline = Instant::now() + window;
+    let mut events = vec![];
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() { break }
+
+        match reader.read_events_timeout(remaining)? {
+            // socket has closed, which will be noticed on the next read
+            Some(more) if more.is_empty() => { break }
+            Some(more) => { events.extend(more) }
+            None => { break }
+        }
+    }
+
+    Ok(events)
+}
+
+/// Watches the target and calls the callback with the current data and after every relevant event.
+/// Only returns with an error, e.g. once the event socket has been closed.
+pub fn watch(target: Target, options: WatchOptions, mut callback: impl FnMut(anyhow::Result<Update>)) -> anyhow::Result<()> {
+    for update in Watcher::new(target, options)? {
+        callback(update);
+    }
+
+    Err(anyhow!("hyprland event socket has closed"))
+}